@@ -1,13 +1,23 @@
+#[allow(clippy::needless_return)]
 pub mod elaborate {
     use serde::{Deserialize, Serialize, de::DeserializeOwned};
     use serde_json::to_string;
     use std::{
-        collections::HashMap,
-        fmt::{Debug, Display},
-        fs::{self, DirEntry, File},
-        hash::{DefaultHasher, Hash, Hasher},
-        io::{BufReader, Read, Write},
-        path::Path,
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet, btree_map},
+        fmt::{self, Debug, Display, Formatter},
+        fs::{self, File, OpenOptions, TryLockError},
+        hash::{BuildHasher, DefaultHasher, Hash, Hasher, RandomState},
+        io::Write,
+        marker::PhantomData,
+        ops::{Bound, RangeBounds},
+        path::{Path, PathBuf},
+        process,
+        sync::{
+            Arc, Condvar, Mutex, MutexGuard, Weak,
+            atomic::{AtomicU64, Ordering},
+        },
+        thread::{self, ThreadId},
+        time::{Duration, SystemTime, UNIX_EPOCH},
     };
 
     #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -33,875 +43,6870 @@ pub mod elaborate {
         CollectReadError,
         /// an error made for atomic checker
         CheckError,
+        /// error encoding or decoding a table format
+        FormatError,
+        /// a record with the same primary key already exists
+        DuplicateKey,
+        /// error reading or writing a secondary index
+        IndexError,
+        /// another process has the database open
+        Locked,
+        /// the database was opened read only
+        ReadOnly,
+        /// the record changed since the expected version was read
+        Conflict,
+        /// the destination of a snapshot already holds files
+        NotEmpty,
+        /// the table name is taken by a file the database keeps for itself
+        ReservedName,
         /// not ideal but meant for ease of use with Commit
         #[default]
         None,
     }
 
-    pub fn write_hash<T>(item: T) -> u64
-    where
-        T: Hash,
-    {
-        let mut hasher = DefaultHasher::new();
-        item.hash(&mut hasher);
-        hasher.finish()
+    /// Storage for named blobs of bytes.
+    /// every read and write done by concept_db goes through one of these.
+    pub trait StorageBackend: Debug + Send + Sync {
+        /// reads the blob called name
+        fn read(&self, name: &str) -> Result<Vec<u8>, TErrors>;
+        /// writes the blob called name, replacing any previous contents
+        fn write(&self, name: &str, data: &[u8]) -> Result<(), TErrors>;
+        /// renames blob from to blob to, replacing to if it exists
+        fn rename(&self, from: &str, to: &str) -> Result<(), TErrors>;
+        /// lists the names of every blob
+        fn list(&self) -> Result<Vec<String>, TErrors>;
+        /// deletes the blob called name. deleting a missing blob is not an error
+        fn delete(&self, name: &str) -> Result<(), TErrors>;
+        /// returns true if the blob called name exists
+        fn exists(&self, name: &str) -> bool;
+        /// adds data to the end of the blob called name, creating it if missing
+        fn append(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            let mut current: Vec<u8> = match self.read(name) {
+                Ok(current) => current,
+                Err(TErrors::FileNotFound) => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            current.extend_from_slice(data);
+            self.write(name, &current)
+        }
+        /// locks the blob called name, creating it if missing, until the BlobLock drops.
+        /// an exclusive lock keeps out every other, a shared one only exclusive ones.
+        /// None when another holder has it and wait is false.
+        /// locks are held against other processes and other handles of this one,
+        /// backends that cant do that hand out locks holding nothing
+        fn lock(
+            &self,
+            name: &str,
+            exclusive: bool,
+            wait: bool,
+        ) -> Result<Option<BlobLock>, TErrors> {
+            let _ = (name, exclusive, wait);
+            Ok(Some(BlobLock::default()))
+        }
     }
 
-    /// An output design for the Logger
-    pub struct Commit<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
-        pub success: bool,
-        pub package: Result<T, TErrors>,
-        pub collection: Result<Collection<T>, TErrors>,
+    #[derive(Debug, Default)]
+    /// A lock on a blob taken through StorageBackend::lock, released when dropped
+    pub struct BlobLock {
+        _file: Option<File>,
     }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Default
-        for Commit<T>
-    {
-        fn default() -> Self {
+    #[derive(Debug, Clone)]
+    /// StorageBackend keeping every blob as a file inside a root directory
+    pub struct FileBackend {
+        root: PathBuf,
+    }
+
+    impl FileBackend {
+        /// creates new instance rooted at root. the directory is created on first write
+        pub fn new<P: AsRef<Path>>(root: P) -> Self {
             Self {
-                success: false,
-                package: Err(TErrors::default()),
-                collection: Err(TErrors::default()),
+                root: root.as_ref().to_path_buf(),
+            }
+        }
+
+        /// root directory of the backend
+        pub fn root(&self) -> &Path {
+            &self.root
+        }
+
+        /// creates the root directory if it doesnt exist yet
+        fn ensure_root(&self) -> Result<(), TErrors> {
+            if !self.root.exists() {
+                fs::create_dir_all(&self.root).map_err(|_| {
+                    println!("Error creating the directory: '{}'", self.root.display());
+                    return TErrors::DirError;
+                })?;
             }
+            Ok(())
         }
     }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Commit<T> {
-        pub fn determine(
+    impl StorageBackend for FileBackend {
+        fn read(&self, name: &str) -> Result<Vec<u8>, TErrors> {
+            let path: PathBuf = self.root.join(name);
+
+            if !path.exists() {
+                return Err(TErrors::FileNotFound);
+            }
+
+            fs::read(path).map_err(|_| {
+                return TErrors::ReadByteError;
+            })
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.ensure_root()?;
+
+            let Ok(mut file) = File::create(self.root.join(name)) else {
+                println!("failed to create file: '{}'", name);
+                return Err(TErrors::FileError);
+            };
+
+            file.write_all(data).map_err(|_| {
+                return TErrors::WriteByteError;
+            })?;
+
+            file.sync_all().map_err(|_| {
+                return TErrors::WriteByteError;
+            })
+        }
+
+        fn rename(&self, from: &str, to: &str) -> Result<(), TErrors> {
+            fs::rename(self.root.join(from), self.root.join(to)).map_err(|_| {
+                println!("Failed to rename file");
+                return TErrors::FileError;
+            })
+        }
+
+        fn list(&self) -> Result<Vec<String>, TErrors> {
+            let Ok(dir) = fs::read_dir(&self.root) else {
+                return Err(TErrors::DirError);
+            };
+
+            Ok(dir
+                .filter_map(|f| f.ok())
+                .filter(|f| f.path().is_file())
+                .map(|f| f.file_name().to_string_lossy().to_string())
+                .collect())
+        }
+
+        fn delete(&self, name: &str) -> Result<(), TErrors> {
+            let path: PathBuf = self.root.join(name);
+
+            if !path.is_file() {
+                return Ok(());
+            }
+
+            fs::remove_file(path).map_err(|_| {
+                return TErrors::DeleteError;
+            })
+        }
+
+        fn exists(&self, name: &str) -> bool {
+            self.root.join(name).is_file()
+        }
+
+        fn append(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.ensure_root()?;
+
+            let Ok(mut file) = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.root.join(name))
+            else {
+                println!("failed to open file: '{}'", name);
+                return Err(TErrors::FileError);
+            };
+
+            file.write_all(data).map_err(|_| {
+                return TErrors::WriteByteError;
+            })?;
+
+            file.sync_all().map_err(|_| {
+                return TErrors::WriteByteError;
+            })
+        }
+
+        /// the lock is an OS file lock, so the kernel drops it with a process that dies
+        fn lock(
             &self,
-            package: Result<T, TErrors>,
-            collection: Result<Collection<T>, TErrors>,
-        ) -> Self {
-            let success: bool = if package.is_err() && collection.is_err() {
-                false
-            } else {
-                true
+            name: &str,
+            exclusive: bool,
+            wait: bool,
+        ) -> Result<Option<BlobLock>, TErrors> {
+            self.ensure_root()?;
+            let path: PathBuf = self.root.join(name);
+
+            let file: File = match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+            {
+                Ok(file) => file,
+                Err(_) if !exclusive => match File::open(&path) {
+                    Ok(file) => file,
+                    // nothing can be written to the directory, so nobody is writing
+                    Err(_) => return Ok(Some(BlobLock::default())),
+                },
+                Err(_) => {
+                    println!("failed to open lock file: '{}'", name);
+                    return Err(TErrors::FileError);
+                }
+            };
+
+            let locked: Result<(), TryLockError> = match (exclusive, wait) {
+                (true, true) => file.lock().map_err(TryLockError::Error),
+                (false, true) => file.lock_shared().map_err(TryLockError::Error),
+                (true, false) => file.try_lock(),
+                (false, false) => file.try_lock_shared(),
+            };
+            match locked {
+                Ok(()) => Ok(Some(BlobLock { _file: Some(file) })),
+                Err(TryLockError::WouldBlock) => Ok(None),
+                Err(TryLockError::Error(_)) => Err(TErrors::FileError),
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    /// StorageBackend that never touches the disk.
+    /// contents are lost once every handle to it is dropped.
+    pub struct MemoryBackend {
+        blobs: Mutex<BTreeMap<String, Vec<u8>>>,
+    }
+
+    impl MemoryBackend {
+        /// creates new empty instance
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        fn blobs(&self) -> Result<MutexGuard<'_, BTreeMap<String, Vec<u8>>>, TErrors> {
+            self.blobs.lock().map_err(|_| {
+                return TErrors::FileError;
+            })
+        }
+    }
+
+    impl StorageBackend for MemoryBackend {
+        fn read(&self, name: &str) -> Result<Vec<u8>, TErrors> {
+            self.blobs()?.get(name).cloned().ok_or(TErrors::FileNotFound)
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.blobs()?.insert(name.to_string(), data.to_vec());
+            Ok(())
+        }
+
+        fn rename(&self, from: &str, to: &str) -> Result<(), TErrors> {
+            let mut blobs = self.blobs()?;
+            let Some(data) = blobs.remove(from) else {
+                println!("Failed to rename file");
+                return Err(TErrors::FileError);
             };
+            blobs.insert(to.to_string(), data);
+            Ok(())
+        }
+
+        fn list(&self) -> Result<Vec<String>, TErrors> {
+            Ok(self.blobs()?.keys().cloned().collect())
+        }
+
+        fn delete(&self, name: &str) -> Result<(), TErrors> {
+            self.blobs()?.remove(name);
+            Ok(())
+        }
+
+        fn exists(&self, name: &str) -> bool {
+            self.blobs().is_ok_and(|blobs| blobs.contains_key(name))
+        }
+
+        fn append(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.blobs()?
+                .entry(name.to_string())
+                .or_default()
+                .extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    /// Blobs as they were when a read view opened, saved just before a writer changed them.
+    /// None for a blob that didnt exist yet
+    struct ViewState {
+        saved: Mutex<HashMap<String, Option<Vec<u8>>>>,
+    }
+
+    #[derive(Debug)]
+    /// StorageBackend wrapping the one a Database was created with.
+    /// keeps the old contents of every blob changed while a read view is open
+    struct Versioned {
+        inner: Arc<dyn StorageBackend>,
+        views: Mutex<Vec<Weak<ViewState>>>,
+    }
 
+    impl Versioned {
+        fn new(inner: Arc<dyn StorageBackend>) -> Self {
             Self {
-                success,
-                package,
-                collection,
+                inner,
+                views: Mutex::new(Vec::new()),
+            }
+        }
+
+        /// starts keeping old versions for a new view until it is dropped
+        fn open_view(&self) -> Result<Arc<ViewState>, TErrors> {
+            let state: Arc<ViewState> = Arc::new(ViewState::default());
+            let mut views = self.views.lock().map_err(|_| TErrors::FileError)?;
+            views.push(Arc::downgrade(&state));
+            Ok(state)
+        }
+
+        /// saves names as they are now for every open view that hasnt saved them,
+        /// then runs change. no view can read names in between
+        fn changing<R>(
+            &self,
+            names: &[&str],
+            change: impl FnOnce(&dyn StorageBackend) -> Result<R, TErrors>,
+        ) -> Result<R, TErrors> {
+            let mut views = self.views.lock().map_err(|_| TErrors::FileError)?;
+            views.retain(|v| v.strong_count() > 0);
+            if views.is_empty() {
+                drop(views);
+                return change(self.inner.as_ref());
             }
+
+            for view in views.iter().filter_map(Weak::upgrade) {
+                let mut saved = view.saved.lock().map_err(|_| TErrors::FileError)?;
+                for name in names {
+                    if saved.contains_key(*name) {
+                        continue;
+                    }
+                    let current: Option<Vec<u8>> = match self.inner.read(name) {
+                        Ok(data) => Some(data),
+                        Err(TErrors::FileNotFound) => None,
+                        Err(e) => return Err(e),
+                    };
+                    saved.insert(name.to_string(), current);
+                }
+            }
+            change(self.inner.as_ref())
+        }
+
+        /// name as view sees it
+        fn read_as_of(&self, view: &ViewState, name: &str) -> Result<Vec<u8>, TErrors> {
+            // held so name cannot change between the lookup and the read
+            let _views = self.views.lock().map_err(|_| TErrors::FileError)?;
+            let saved = view.saved.lock().map_err(|_| TErrors::FileError)?;
+            match saved.get(name) {
+                Some(Some(data)) => Ok(data.clone()),
+                Some(None) => Err(TErrors::FileNotFound),
+                None => self.inner.read(name),
+            }
+        }
+
+        /// every blob view sees
+        fn list_as_of(&self, view: &ViewState) -> Result<Vec<String>, TErrors> {
+            let _views = self.views.lock().map_err(|_| TErrors::FileError)?;
+            let saved = view.saved.lock().map_err(|_| TErrors::FileError)?;
+            let mut names: BTreeSet<String> = self
+                .inner
+                .list()?
+                .into_iter()
+                .filter(|name| !saved.contains_key(name))
+                .collect();
+            names.extend(
+                saved
+                    .iter()
+                    .filter(|(_, data)| data.is_some())
+                    .map(|(name, _)| name.clone()),
+            );
+            Ok(names.into_iter().collect())
         }
     }
 
-    pub trait Collect<T: Serialize + DeserializeOwned + Sized + Clone + Debug> {
-        /// creates new instance
-        fn new(inner: Vec<T>) -> Self;
-        /// collects all tables across the JSON files that match type of T.
-        fn collect(&self, frag: Fragment<T>) -> Result<Self, TErrors>
-        where
-            Self: Sized;
-        /// adds a table of type T to collection.
-        fn append(&mut self, obj: T) -> Self;
-        /// removes an object of type T from the collection.
-        fn remove(&self, obj: T) -> Self
-        where
-            T: PartialEq;
-        /// update an index to the provided object of type T.
-        fn update_index(&self, index: usize, new_obj: T) -> Self;
-        /// write collection to json file.  
-        fn write_to_file(&self, title: String) -> Result<(), TErrors>
-        where
-            Self: Serialize + DeserializeOwned + Clone + Debug;
+    impl StorageBackend for Versioned {
+        fn read(&self, name: &str) -> Result<Vec<u8>, TErrors> {
+            self.inner.read(name)
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.changing(&[name], |b| b.write(name, data))
+        }
+
+        fn rename(&self, from: &str, to: &str) -> Result<(), TErrors> {
+            self.changing(&[from, to], |b| b.rename(from, to))
+        }
+
+        fn list(&self) -> Result<Vec<String>, TErrors> {
+            self.inner.list()
+        }
+
+        fn delete(&self, name: &str) -> Result<(), TErrors> {
+            self.changing(&[name], |b| b.delete(name))
+        }
+
+        fn exists(&self, name: &str) -> bool {
+            self.inner.exists(name)
+        }
+
+        fn append(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.changing(&[name], |b| b.append(name, data))
+        }
+
+        fn lock(
+            &self,
+            name: &str,
+            exclusive: bool,
+            wait: bool,
+        ) -> Result<Option<BlobLock>, TErrors> {
+            self.inner.lock(name, exclusive, wait)
+        }
     }
 
-    /// covers the different kinds of HashMap that may be required for conversion
-    /// from intended struct.
-    pub trait ToHash {
-        /// converts T to HashMap.
-        fn to_hash(&self) -> Result<HashMap<String, String>, TErrors>;
-        /// converts T to HashMap of String and Option of String.
-        fn to_hash_opt(&self) -> Result<HashMap<String, Option<String>>, TErrors>;
-        /// converts T to HashMap of String and Vec of String.
-        fn to_hash_vec(&self) -> Result<HashMap<String, Vec<String>>, TErrors>;
-        /// converts hashmap to Vec of tuple of String and String.
-        fn zip(&self) -> Result<Vec<(String, String)>, TErrors>;
+    #[derive(Debug)]
+    /// Read only StorageBackend showing a Database as it was when the view opened
+    struct ViewBackend {
+        versions: Arc<Versioned>,
+        state: Arc<ViewState>,
     }
 
-    pub trait ToLogAtomic<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
-        /// create new Atomic Logger instance 
-        fn new(prior: T, later: AtomicCopy, time_stamp: String) -> Self; 
-        /// write ids based on hash of prior and later
-        fn set_hash_ids(&self) -> Result<Self, TErrors>
-        where
-            Self: Sized;
-        /// compares two hashes from original and altered states
-        fn compare_ids(&self) -> bool;
-        /// updates initial state
-        fn set_prior(&self, prior: T) -> Self;
-        /// intended to set updated state on completion for comparision
-        fn set_later(&self, later: AtomicCopy) -> Self;
-        /// write changes to logger
-        fn document(&self) -> Result<(), TErrors>;
-        /// sets the time at which change occured.
-        fn set_time_stamp(&self, time_stamp: String) -> Self;
-        /// this is experimental. it wont work for HashMap of String and Vec of T
-        fn raw_changes(&self) -> Result<(Vec<(String, String)>, Vec<(String, String)>), TErrors>;
-        /// measures success of execution
-        fn commit(&self) -> Result<Commit<AtomicCopy>, TErrors>;
-        /// returns true if successful operation
-        fn is_success(&self) -> Result<bool, TErrors>;
-        /// returns true if failed operation
-        fn is_failure(&self) -> Result<bool, TErrors>;
-        /// rollsback to original state on error
-        fn rollback(&self) -> Result<AtomicLogger<T>, TErrors>;
+    impl StorageBackend for ViewBackend {
+        fn read(&self, name: &str) -> Result<Vec<u8>, TErrors> {
+            self.versions.read_as_of(&self.state, name)
+        }
+
+        fn write(&self, _name: &str, _data: &[u8]) -> Result<(), TErrors> {
+            Err(TErrors::ReadOnly)
+        }
+
+        fn rename(&self, _from: &str, _to: &str) -> Result<(), TErrors> {
+            Err(TErrors::ReadOnly)
+        }
+
+        fn list(&self) -> Result<Vec<String>, TErrors> {
+            self.versions.list_as_of(&self.state)
+        }
+
+        fn delete(&self, _name: &str) -> Result<(), TErrors> {
+            Err(TErrors::ReadOnly)
+        }
+
+        fn exists(&self, name: &str) -> bool {
+            self.read(name).is_ok()
+        }
+    }
+
+    #[derive(Debug)]
+    /// StorageBackend of a database that failed to open, failing every call with why
+    struct Unopened {
+        error: TErrors,
+    }
+
+    impl StorageBackend for Unopened {
+        fn read(&self, _name: &str) -> Result<Vec<u8>, TErrors> {
+            Err(self.error.clone())
+        }
+
+        fn write(&self, _name: &str, _data: &[u8]) -> Result<(), TErrors> {
+            Err(self.error.clone())
+        }
+
+        fn rename(&self, _from: &str, _to: &str) -> Result<(), TErrors> {
+            Err(self.error.clone())
+        }
+
+        fn list(&self) -> Result<Vec<String>, TErrors> {
+            Err(self.error.clone())
+        }
+
+        fn delete(&self, _name: &str) -> Result<(), TErrors> {
+            Err(self.error.clone())
+        }
+
+        fn exists(&self, _name: &str) -> bool {
+            false
+        }
+
+        fn lock(
+            &self,
+            _name: &str,
+            _exclusive: bool,
+            _wait: bool,
+        ) -> Result<Option<BlobLock>, TErrors> {
+            Err(self.error.clone())
+        }
+    }
+
+    /// Source of the current time for log entries.
+    /// swap in a ManualClock for deterministic time stamps.
+    pub trait Clock: Debug + Send + Sync {
+        /// the current time
+        fn now(&self) -> SystemTime;
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    /// Clock reading the system time
+    pub struct SystemClock;
+
+    impl Clock for SystemClock {
+        fn now(&self) -> SystemTime {
+            SystemTime::now()
+        }
+    }
+
+    #[derive(Debug, Default)]
+    /// Clock that only moves when told to
+    pub struct ManualClock {
+        nanos: AtomicU64,
     }
 
-    /// simplifies code in ToHash trait
-    // why did I even make this??
-    trait ToHashOpt {
-        /// converts T to HashMap of String and Option of String.
-        fn convert_opt(&self) -> HashMap<String, Option<String>>;
-    }
+    impl ManualClock {
+        /// creates new instance stopped at time
+        pub fn new(time: SystemTime) -> Self {
+            let clock: Self = Self::default();
+            clock.set(time);
+            clock
+        }
+
+        /// moves the clock to time
+        pub fn set(&self, time: SystemTime) {
+            let nanos: u64 = time
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default();
+            self.nanos.store(nanos, Ordering::SeqCst);
+        }
+
+        /// moves the clock forward by by
+        pub fn advance(&self, by: Duration) {
+            self.nanos.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> SystemTime {
+            UNIX_EPOCH + Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+        }
+    }
+
+    /// formats time as an RFC 3339 UTC time stamp with nanoseconds,
+    /// e.g. '2024-05-01T09:30:00.000000000Z'. times before 1970 are clamped
+    pub fn rfc3339(time: SystemTime) -> String {
+        let since: Duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs: u64 = since.as_secs();
+        let (hour, minute, second) = (secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+        // civil date from days since 1970-01-01, after Howard Hinnant
+        let days: i64 = (secs / 86400) as i64 + 719468;
+        let era: i64 = days / 146097;
+        let day_of_era: i64 = days - era * 146097;
+        let year_of_era: i64 =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year: i64 =
+            day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp: i64 = (5 * day_of_year + 2) / 153;
+        let day: i64 = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month: i64 = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year: i64 = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            since.subsec_nanos()
+        )
+    }
+
+    #[derive(Debug, Default)]
+    /// Lets any number of writers through at once, or one snapshot with no writers.
+    /// writers may enter again while inside, a snapshot waits for every writer to leave.
+    /// new writers wait behind a waiting snapshot, so a steady stream of them cant starve it
+    struct Gate {
+        state: Mutex<GateState>,
+        changed: Condvar,
+    }
+
+    #[derive(Debug, Default)]
+    /// writers inside by thread, whether a snapshot holds the gate
+    /// and how many snapshots wait for it
+    struct GateState {
+        writers: HashMap<ThreadId, usize>,
+        frozen: bool,
+        pending: usize,
+    }
+
+    impl Gate {
+        /// waits out any snapshot, held or waiting, then lets a writer in until the guard drops.
+        /// a writer already inside goes straight in, a waiting snapshot waits on it
+        fn enter(&self) -> Result<GateGuard<'_>, TErrors> {
+            let me: ThreadId = thread::current().id();
+            let mut state = self.state.lock().map_err(|_| TErrors::FileError)?;
+            if !state.writers.contains_key(&me) {
+                while state.frozen || state.pending > 0 {
+                    state = self.changed.wait(state).map_err(|_| TErrors::FileError)?;
+                }
+            }
+            *state.writers.entry(me).or_default() += 1;
+            Ok(GateGuard {
+                gate: self,
+                writer: Some(me),
+            })
+        }
+
+        /// waits for every writer to leave, then keeps writers out until the guard drops
+        fn freeze(&self) -> Result<GateGuard<'_>, TErrors> {
+            let mut state = self.state.lock().map_err(|_| TErrors::FileError)?;
+            state.pending += 1;
+            while !state.writers.is_empty() || state.frozen {
+                state = self.changed.wait(state).map_err(|_| TErrors::FileError)?;
+            }
+            state.pending -= 1;
+            state.frozen = true;
+            Ok(GateGuard {
+                gate: self,
+                writer: None,
+            })
+        }
+    }
+
+    /// holds a Gate open for the writer thread or frozen for a snapshot
+    struct GateGuard<'a> {
+        gate: &'a Gate,
+        writer: Option<ThreadId>,
+    }
+
+    impl Drop for GateGuard<'_> {
+        fn drop(&mut self) {
+            if let Ok(mut state) = self.gate.state.lock() {
+                match self.writer {
+                    Some(writer) => {
+                        if let Some(depth) = state.writers.get_mut(&writer) {
+                            *depth -= 1;
+                            if *depth == 0 {
+                                state.writers.remove(&writer);
+                            }
+                        }
+                    }
+                    None => state.frozen = false,
+                }
+            }
+            self.gate.changed.notify_all();
+        }
+    }
+
+    #[derive(Debug, Default)]
+    /// Reader/writer lock of one table. any number of readers or one writer.
+    /// the writer may take the lock again, and read, while it holds it
+    struct TableLock {
+        state: Mutex<LockState>,
+        changed: Condvar,
+    }
+
+    #[derive(Debug, Default)]
+    /// readers inside, the thread writing and how many times it took the lock
+    struct LockState {
+        readers: usize,
+        writer: Option<ThreadId>,
+        depth: usize,
+    }
+
+    impl TableLock {
+        /// waits for the writer to leave unless it is this thread
+        fn read(self: Arc<Self>) -> Result<TableGuard, TErrors> {
+            let me: ThreadId = thread::current().id();
+            let mut state = self.state.lock().map_err(|_| TErrors::FileError)?;
+            if state.writer == Some(me) {
+                state.depth += 1;
+            } else {
+                while state.writer.is_some() {
+                    state = self.changed.wait(state).map_err(|_| TErrors::FileError)?;
+                }
+                state.readers += 1;
+            }
+            let writer: bool = state.writer == Some(me);
+            drop(state);
+            Ok(TableGuard { lock: self, writer })
+        }
+
+        /// true when this thread holds the write lock
+        fn is_writer(&self) -> Result<bool, TErrors> {
+            let state = self.state.lock().map_err(|_| TErrors::FileError)?;
+            Ok(state.writer == Some(thread::current().id()))
+        }
+
+        /// waits for every reader and any other writer to leave
+        fn write(self: Arc<Self>) -> Result<TableGuard, TErrors> {
+            let me: ThreadId = thread::current().id();
+            let mut state = self.state.lock().map_err(|_| TErrors::FileError)?;
+            if state.writer != Some(me) {
+                while state.writer.is_some() || state.readers > 0 {
+                    state = self.changed.wait(state).map_err(|_| TErrors::FileError)?;
+                }
+                state.writer = Some(me);
+            }
+            state.depth += 1;
+            drop(state);
+            Ok(TableGuard {
+                lock: self,
+                writer: true,
+            })
+        }
+    }
+
+    /// holds a TableLock for reading or writing until dropped
+    struct TableGuard {
+        lock: Arc<TableLock>,
+        writer: bool,
+    }
+
+    impl Drop for TableGuard {
+        fn drop(&mut self) {
+            if let Ok(mut state) = self.lock.state.lock() {
+                if !self.writer {
+                    state.readers -= 1;
+                } else {
+                    state.depth -= 1;
+                    if state.depth == 0 {
+                        state.writer = None;
+                    }
+                }
+            }
+            self.lock.changed.notify_all();
+        }
+    }
+
+    #[derive(Debug, Default)]
+    /// One TableLock per table or bookkeeping file, created on first use
+    struct Locks {
+        tables: Mutex<HashMap<String, Arc<TableLock>>>,
+    }
+
+    impl Locks {
+        /// the lock of name
+        fn of(&self, name: &str) -> Result<Arc<TableLock>, TErrors> {
+            let mut tables = self.tables.lock().map_err(|_| TErrors::FileError)?;
+            Ok(tables.entry(name.to_string()).or_default().clone())
+        }
+    }
+
+    /// write locks on tables and the open gate they were taken through.
+    /// the lock files go first, then the tables, then the gate
+    struct Writing<'a> {
+        _marks: Vec<BlobLock>,
+        _tables: Vec<TableGuard>,
+        _open: Option<GateGuard<'a>>,
+    }
+
+    /// file locked by the process that has the database open for writing
+    const LOCK_FILE: &str = "LOCK";
+    /// extension of the file locked while its table is being written
+    const LOCK_EXT: &str = "lock";
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    /// What Database::open_with does when another process has the database open
+    pub enum OpenMode {
+        /// waits for the other process to close it
+        Wait,
+        /// fails with TErrors::Locked
+        FailFast,
+        /// opens without writing. every write fails with TErrors::ReadOnly
+        ReadOnly,
+    }
+
+    #[derive(Debug)]
+    /// the LOCK file of a database, held until every handle sharing it drops
+    struct ProcessLock {
+        _lock: BlobLock,
+        /// state every handle opened on the same root shares
+        shared: Database,
+    }
+
+    /// roots this process has open for writing
+    static OPEN: Mutex<Vec<(PathBuf, Weak<ProcessLock>)>> = Mutex::new(Vec::new());
+
+    /// true for the LOCK file and the lock files of tables
+    fn is_lock(name: &str) -> bool {
+        name == LOCK_FILE || name.ends_with(&format!(".{}", LOCK_EXT))
+    }
+
+    #[derive(Debug, Clone)]
+    /// Handle to a database stored through a StorageBackend.
+    /// every Fragment, Collection, AtomicCopy and AtomicLogger
+    /// operation is routed through one of these.
+    pub struct Database {
+        backend: Arc<dyn StorageBackend>,
+        versions: Arc<Versioned>,
+        format: Format,
+        table_formats: HashMap<String, Format>,
+        clock: Arc<dyn Clock>,
+        gate: Arc<Gate>,
+        locks: Arc<Locks>,
+        read_only: bool,
+        owner: Option<Arc<ProcessLock>>,
+        /// temp files staged by Transactions not yet committed or aborted
+        staging: Arc<Mutex<HashSet<String>>>,
+        /// fields of every secondary index, listed on first use
+        indexed: Arc<Mutex<Option<BTreeSet<String>>>>,
+    }
+
+    impl Default for Database {
+        /// the historical location of the db: './db_files/', opened with OpenMode::FailFast.
+        /// when it cant be opened every operation fails with the reason, such as Locked
+        fn default() -> Self {
+            Self::open_or_unopened("./db_files/")
+        }
+    }
+
+    impl Database {
+        /// creates new instance on top of any StorageBackend
+        pub fn new<B: StorageBackend + 'static>(backend: B) -> Self {
+            let versions: Arc<Versioned> = Arc::new(Versioned::new(Arc::new(backend)));
+            Self {
+                backend: versions.clone(),
+                versions,
+                format: Format::default(),
+                table_formats: HashMap::new(),
+                clock: Arc::new(SystemClock),
+                gate: Arc::new(Gate::default()),
+                locks: Arc::new(Locks::default()),
+                read_only: false,
+                owner: None,
+                staging: Arc::new(Mutex::new(HashSet::new())),
+                indexed: Arc::new(Mutex::new(None)),
+            }
+        }
+
+        /// opens (and creates if missing) a database rooted at root.
+        /// writes interrupted by a crash are recovered first.
+        /// fails with Locked while another process has it open
+        pub fn open<P: AsRef<Path>>(root: P) -> Result<Self, TErrors> {
+            Self::open_with(root, OpenMode::FailFast)
+        }
+
+        /// open, with mode deciding what happens when another process has it open.
+        /// opening a root this process already has open shares that handle.
+        /// the database stays locked until every handle on it is dropped
+        pub fn open_with<P: AsRef<Path>>(root: P, mode: OpenMode) -> Result<Self, TErrors> {
+            let backend: FileBackend = FileBackend::new(root);
+            backend.ensure_root()?;
+            let Ok(root) = backend.root().canonicalize() else {
+                return Err(TErrors::FileError);
+            };
+            let mut db: Database = Self::new(backend);
+            if mode == OpenMode::ReadOnly {
+                db.read_only = true;
+                return Ok(db);
+            }
+
+            // held while waiting, so two threads opening one root end up sharing it
+            let mut open = OPEN.lock().map_err(|_| TErrors::FileError)?;
+            open.retain(|(_, held)| held.strong_count() > 0);
+            let held: Option<Arc<ProcessLock>> = open
+                .iter()
+                .find(|(path, _)| *path == root)
+                .and_then(|(_, held)| held.upgrade());
+            if let Some(held) = held {
+                return Ok(Self {
+                    owner: Some(held.clone()),
+                    ..held.shared.clone()
+                });
+            }
+
+            let Some(lock) = db.backend.lock(LOCK_FILE, true, mode == OpenMode::Wait)? else {
+                return Err(TErrors::Locked);
+            };
+            let held: Arc<ProcessLock> = Arc::new(ProcessLock {
+                _lock: lock,
+                shared: db.clone(),
+            });
+            db.owner = Some(held.clone());
+            db.recover()?;
+            open.push((root, Arc::downgrade(&held)));
+            Ok(db)
+        }
+
+        /// open without waiting, deferring a failure to every operation on the handle
+        fn open_or_unopened<P: AsRef<Path>>(root: P) -> Self {
+            Self::open(root).unwrap_or_else(|error| Self::new(Unopened { error }))
+        }
+
+        /// true when opened with OpenMode::ReadOnly
+        pub fn is_read_only(&self) -> bool {
+            self.read_only
+        }
+
+        /// opens a read only view of the database as it is now. writes made later
+        /// through this handle or its clones dont show in the view. their old
+        /// versions are kept until every clone of the view is dropped
+        pub fn read_view(&self) -> Result<Database, TErrors> {
+            // no write is half done while the view opens
+            let _frozen = self.gate.freeze()?;
+            let backend: ViewBackend = ViewBackend {
+                versions: self.versions.clone(),
+                state: self.versions.open_view()?,
+            };
+            Ok(Self {
+                read_only: true,
+                format: self.format,
+                table_formats: self.table_formats.clone(),
+                clock: self.clock.clone(),
+                ..Self::new(backend)
+            })
+        }
+
+        /// fails with ReadOnly when the database was opened read only
+        fn writable(&self) -> Result<(), TErrors> {
+            if self.read_only {
+                return Err(TErrors::ReadOnly);
+            }
+            Ok(())
+        }
+
+        /// creates a database that only lives in memory
+        pub fn in_memory() -> Self {
+            Self::new(MemoryBackend::new())
+        }
+
+        /// the StorageBackend every operation goes through
+        pub fn backend(&self) -> &dyn StorageBackend {
+            self.backend.as_ref()
+        }
+
+        /// sets the format new tables are written in
+        pub fn set_format(&self, format: Format) -> Self {
+            Self {
+                format,
+                ..self.clone()
+            }
+        }
+
+        /// sets the clock log entries are stamped with
+        pub fn set_clock<C: Clock + 'static>(&self, clock: C) -> Self {
+            Self {
+                clock: Arc::new(clock),
+                ..self.clone()
+            }
+        }
+
+        /// the current time of the clock as an RFC 3339 UTC time stamp
+        pub fn time_stamp(&self) -> String {
+            rfc3339(self.clock.now())
+        }
+
+        /// sets the format a single table is written in
+        pub fn set_table_format(&self, table: String, format: Format) -> Self {
+            let mut table_formats: HashMap<String, Format> = self.table_formats.clone();
+            table_formats.insert(table, format);
+            Self {
+                table_formats,
+                ..self.clone()
+            }
+        }
+
+        /// format the table is written in. an explicit table format wins,
+        /// then the format the table is already stored in, then the database format.
+        pub fn table_format(&self, table: &str) -> Format {
+            if let Some(format) = self.table_formats.get(table) {
+                return *format;
+            }
+            Format::ALL
+                .into_iter()
+                .find(|f| self.backend.exists(&blob_name(table, f.ext())))
+                .unwrap_or(self.format)
+        }
+
+        /// lets a writer through the gate and write locks tables in name order,
+        /// so writers of different tables never wait on one another
+        fn lock_tables(&self, tables: &[&str]) -> Result<Writing<'_>, TErrors> {
+            self.writable()?;
+            let open: GateGuard<'_> = self.gate.enter()?;
+            let mut writing: Writing<'_> = self.hold_tables(tables)?;
+            writing._open = Some(open);
+            Ok(writing)
+        }
+
+        /// lock_tables for callers already holding the gate.
+        /// the lock file of each table keeps out readers in other processes
+        fn hold_tables(&self, tables: &[&str]) -> Result<Writing<'_>, TErrors> {
+            self.writable()?;
+            let names: BTreeSet<&str> = tables.iter().copied().collect();
+            let mut writing: Writing<'_> = Writing {
+                _marks: Vec::new(),
+                _tables: Vec::new(),
+                _open: None,
+            };
+            for name in &names {
+                check_table_name(name)?;
+            }
+            for name in names {
+                let lock: Arc<TableLock> = self.locks.of(name)?;
+                // an outer write of this thread holds the lock file already
+                let outer: bool = lock.is_writer()?;
+                writing._tables.push(lock.write()?);
+                if self.owner.is_some() && !outer {
+                    let mark: String = blob_name(name, LOCK_EXT);
+                    writing._marks.extend(self.backend.lock(&mark, true, true)?);
+                }
+            }
+            Ok(writing)
+        }
+
+        /// write locks a bookkeeping file such as the log, the write-ahead log
+        /// or an index. held only while it is read and rewritten
+        fn lock_file(&self, name: &str) -> Result<TableGuard, TErrors> {
+            self.locks.of(name)?.write()
+        }
+
+        /// reads the stored bytes of a table regardless of its format.
+        /// fails with CheckError when they dont match the checksum of the table
+        fn read_blob(&self, table: &str) -> Result<Vec<u8>, TErrors> {
+            let _reading: TableGuard = self.locks.of(table)?.read()?;
+            // the writing process holds the lock file of a table while it writes it
+            let _marked: Option<BlobLock> = match self.read_only {
+                true => self
+                    .backend
+                    .lock(&blob_name(table, LOCK_EXT), false, true)?,
+                false => None,
+            };
+            for format in Format::ALL {
+                match self.backend.read(&blob_name(table, format.ext())) {
+                    Err(TErrors::FileNotFound) => continue,
+                    Ok(data) if !self.sum_matches(table, &data) => return Err(TErrors::CheckError),
+                    other => return other,
+                }
+            }
+            Err(TErrors::FileNotFound)
+        }
+
+        /// keeps repair and recover from deleting a temp file staged by a Transaction
+        fn hold_temp(&self, temp: String) -> Result<(), TErrors> {
+            let mut staging = self.staging.lock().map_err(|_| TErrors::FileError)?;
+            staging.insert(temp);
+            Ok(())
+        }
+
+        /// undoes hold_temp once the temp file is committed or deleted
+        fn release_temp(&self, temp: &str) -> Result<(), TErrors> {
+            let mut staging = self.staging.lock().map_err(|_| TErrors::FileError)?;
+            staging.remove(temp);
+            Ok(())
+        }
+
+        /// temp files of writes that are neither running nor staged, so left by a crash.
+        /// callers hold the gate frozen
+        fn orphaned_temps(&self) -> Result<Vec<String>, TErrors> {
+            let staging = self.staging.lock().map_err(|_| TErrors::FileError)?;
+            let mut orphaned: Vec<String> = self
+                .backend
+                .list()?
+                .into_iter()
+                .filter(|name| name.ends_with(".temp") && !staging.contains(name))
+                .collect();
+            orphaned.sort();
+            Ok(orphaned)
+        }
+
+        /// the stored bytes of table in whichever format it is in, read past its checksum
+        /// so a corrupt table is told apart from a missing one. None when it doesnt exist
+        fn stored_blob(&self, table: &str) -> Option<Vec<u8>> {
+            Format::ALL
+                .into_iter()
+                .find_map(|format| self.backend.read(&blob_name(table, format.ext())).ok())
+        }
+
+        /// deletes a table in every format it may be stored in, with its checksum
+        fn delete_blob(&self, table: &str) -> Result<(), TErrors> {
+            for format in Format::ALL {
+                self.backend.delete(&blob_name(table, format.ext()))?;
+            }
+            self.backend.delete(&blob_name(table, SUM_EXT))
+        }
+
+        /// stores the checksum of the table data next to it
+        fn write_sum(&self, table: &str, data: &[u8]) -> Result<(), TErrors> {
+            self.backend.write(
+                &blob_name(table, SUM_EXT),
+                checksum(data).to_string().as_bytes(),
+            )
+        }
+
+        /// true when data matches the stored checksum of table.
+        /// tables written before checksums were kept always match
+        fn sum_matches(&self, table: &str, data: &[u8]) -> bool {
+            let Ok(stored) = self.backend.read(&blob_name(table, SUM_EXT)) else {
+                return true;
+            };
+            String::from_utf8_lossy(&stored).trim().parse::<u64>() == Ok(checksum(data))
+        }
+
+        /// opens the multi-record table called name
+        pub fn table<T>(&self, name: String) -> Table<T>
+        where
+            T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default,
+        {
+            Table::new(self.clone(), name)
+        }
+
+        /// declares a secondary index on field and builds it from every table.
+        /// the index is kept up to date by every later write
+        pub fn create_index(&self, field: String, kind: IndexKind) -> Result<SecondaryIndex, TErrors> {
+            self.writable()?;
+            let _frozen = self.gate.freeze()?;
+            let index: SecondaryIndex = self.build_index(field, kind)?;
+            self.write_index(&index)?;
+            self.forget_indexes()?;
+            Ok(index)
+        }
+
+        /// builds the index on field from every readable table. callers hold the gate frozen
+        fn build_index(&self, field: String, kind: IndexKind) -> Result<SecondaryIndex, TErrors> {
+            let mut index: SecondaryIndex = SecondaryIndex::new(field, kind);
+            for table in self.stored_tables()? {
+                let Ok(contents) = self.read_blob(&table) else {
+                    continue;
+                };
+                if let Ok(record) = Format::decode::<FieldValue>(&contents) {
+                    index.insert(&table, &indexed_rows(&table, record));
+                }
+            }
+            Ok(index)
+        }
+
+        /// removes the secondary index on field
+        pub fn drop_index(&self, field: &str) -> Result<(), TErrors> {
+            self.writable()?;
+            let _open = self.gate.enter()?;
+            let _index: TableGuard = self.lock_file(&blob_name(field, INDEX_EXT))?;
+            self.backend.delete(&blob_name(field, INDEX_EXT))?;
+            self.forget_indexes()
+        }
+
+        /// fields of every secondary index, listing the backend only the first time
+        fn indexed_fields(&self) -> Result<BTreeSet<String>, TErrors> {
+            let mut indexed = self.indexed.lock().map_err(|_| TErrors::FileError)?;
+            if let Some(fields) = indexed.as_ref() {
+                return Ok(fields.clone());
+            }
+            let fields: BTreeSet<String> = self
+                .backend
+                .list()?
+                .iter()
+                .filter_map(|name| name.strip_suffix(&format!(".{}", INDEX_EXT)))
+                .map(|field| field.to_string())
+                .collect();
+            *indexed = Some(fields.clone());
+            Ok(fields)
+        }
+
+        /// makes indexed_fields list the backend again after indexes were added or removed
+        fn forget_indexes(&self) -> Result<(), TErrors> {
+            *self.indexed.lock().map_err(|_| TErrors::FileError)? = None;
+            Ok(())
+        }
+
+        /// returns the secondary index on field if one was created
+        pub fn index(&self, field: &str) -> Result<Option<SecondaryIndex>, TErrors> {
+            match self.backend.read(&blob_name(field, INDEX_EXT)) {
+                Ok(data) => Ok(Some(SecondaryIndex::from_bytes(&data)?)),
+                Err(TErrors::FileNotFound) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// returns every secondary index
+        pub fn indexes(&self) -> Result<Vec<SecondaryIndex>, TErrors> {
+            let mut indexes: Vec<SecondaryIndex> = Vec::new();
+            for field in self.indexed_fields()? {
+                indexes.extend(self.index(&field)?);
+            }
+            Ok(indexes)
+        }
+
+        /// stores a secondary index next to the tables
+        fn write_index(&self, index: &SecondaryIndex) -> Result<(), TErrors> {
+            self.backend
+                .write(&blob_name(&index.field, INDEX_EXT), &index.to_bytes()?)
+        }
+
+        /// points every secondary index at the new record of table.
+        /// None removes the table from the indexes
+        fn reindex_table(&self, table: &str, record: Option<&FieldValue>) -> Result<(), TErrors> {
+            for field in self.indexed_fields()? {
+                let _index: TableGuard = self.lock_file(&blob_name(&field, INDEX_EXT))?;
+                // another writer may have changed the index since it was listed
+                let Some(mut index) = self.index(&field)? else {
+                    continue;
+                };
+                let before: SecondaryIndex = index.clone();
+                index.remove(table);
+                if let Some(record) = record {
+                    index.insert(table, &indexed_rows(table, record.clone()));
+                }
+                // most writes leave the indexed field alone
+                if index != before {
+                    self.write_index(&index)?;
+                }
+            }
+            Ok(())
+        }
+
+        /// creates a Fragment<T> bound to this database
+        pub fn fragment<T>(&self, inner: T) -> Fragment<T>
+        where
+            T: Serialize + DeserializeOwned + Sized + Clone,
+        {
+            Fragment {
+                inner,
+                db: self.clone(),
+            }
+        }
+
+        /// starts a transaction writing to this database
+        pub fn transaction(&self) -> Transaction {
+            Transaction::new(self.clone())
+        }
+
+        /// every operation documented on table, oldest first.
+        /// a torn last line left by a crash is skipped
+        pub fn log<T>(&self, table: &str) -> Result<Vec<AtomicLogger<T>>, TErrors>
+        where
+            T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default,
+        {
+            let mut entries: Vec<AtomicLogger<T>> = Vec::new();
+            for (entry, prior) in history(&self.log_entries()?, table)? {
+                let Ok(mut entry) = serde_json::from_value::<AtomicLogger<T>>(entry.clone()) else {
+                    return Err(TErrors::FormatError);
+                };
+                if let Some(prior) = prior {
+                    let Ok(prior) = serde_json::from_value::<T>(prior) else {
+                        return Err(TErrors::FormatError);
+                    };
+                    entry.prior = prior;
+                }
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+
+        /// every entry of the log as JSON, oldest first. torn lines are skipped
+        fn log_entries(&self) -> Result<Vec<FieldValue>, TErrors> {
+            let buf: Vec<u8> = match self.backend.read(&blob_name(LOG_TABLE, LOG_EXT)) {
+                Ok(buf) => buf,
+                Err(TErrors::FileNotFound) => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            };
+
+            Ok(buf
+                .split(|b| *b == b'\n')
+                .filter(|l| !l.is_empty())
+                .filter_map(|line| serde_json::from_slice::<FieldValue>(line).ok())
+                .collect())
+        }
+
+        /// rebuilds every table in the log as it was at point,
+        /// reversing the changes logged after it. returns the tables that changed
+        pub fn restore_to<P: Into<RestorePoint>>(&self, point: P) -> Result<Vec<String>, TErrors> {
+            let point: RestorePoint = point.into();
+            let entries: Vec<FieldValue> = self.log_entries()?;
+
+            let mut tables: Vec<String> = Vec::new();
+            for entry in &entries {
+                let Some(table) = entry.get("table").and_then(|t| t.as_str()) else {
+                    continue;
+                };
+                if !tables.iter().any(|t| t == table) {
+                    tables.push(table.to_string());
+                }
+            }
+
+            let mut restored: Vec<String> = Vec::new();
+            for table in tables {
+                if self.restore_from(&entries, &table, &point)? {
+                    restored.push(table);
+                }
+            }
+            Ok(restored)
+        }
+
+        /// rebuilds table as it was at point. returns true if it changed
+        pub fn restore_table_to<P: Into<RestorePoint>>(
+            &self,
+            table: &str,
+            point: P,
+        ) -> Result<bool, TErrors> {
+            self.restore_from(&self.log_entries()?, table, &point.into())
+        }
+
+        /// the state of table at point is the prior state of the first
+        /// successful entry after it. nothing logged after point means nothing to undo
+        fn restore_from(
+            &self,
+            entries: &[FieldValue],
+            table: &str,
+            point: &RestorePoint,
+        ) -> Result<bool, TErrors> {
+            let _writing = self.lock_tables(&[table])?;
+            let Some((_, target)) = history(entries, table)?
+                .into_iter()
+                .filter(|(e, _)| e.get("outcome").and_then(|o| o.as_str()) == Some("Success"))
+                .find(|(e, _)| point.is_before(e))
+            else {
+                return Ok(false);
+            };
+
+            // a corrupt table is never at the target, even when the target is deleted
+            let unchanged: bool = match &target {
+                None => self.stored_blob(table).is_none(),
+                Some(_) => self.current_state(table) == target,
+            };
+            if unchanged {
+                return Ok(false);
+            }
+            self.apply_state(table, target, LogOp::Restore)?;
+            Ok(true)
+        }
+
+        /// reverts the latest change to table that has not been undone.
+        /// returns false when there is nothing to undo
+        pub fn undo(&self, table: &str) -> Result<bool, TErrors> {
+            let _writing = self.lock_tables(&[table])?;
+            let (mut undo, _) = self.undo_stacks(table)?;
+            let Some((_, target)) = undo.pop() else {
+                return Ok(false);
+            };
+            self.apply_state(table, target, LogOp::Undo)?;
+            Ok(true)
+        }
+
+        /// applies again the latest change to table that was undone.
+        /// returns false when there is nothing to redo
+        pub fn redo(&self, table: &str) -> Result<bool, TErrors> {
+            let _writing = self.lock_tables(&[table])?;
+            let (_, mut redo) = self.undo_stacks(table)?;
+            let Some((entry, _)) = redo.pop() else {
+                return Ok(false);
+            };
+            self.apply_state(table, state_after(&entry)?, LogOp::Redo)?;
+            Ok(true)
+        }
+
+        /// changes of table that can be undone and changes that can be redone,
+        /// latest last, each with the state it found the table in.
+        /// a new change clears the redo stack
+        fn undo_stacks(&self, table: &str) -> Result<(Vec<Change>, Vec<Change>), TErrors> {
+            let mut undo: Vec<Change> = Vec::new();
+            let mut redo: Vec<Change> = Vec::new();
+
+            for (entry, prior) in history(&self.log_entries()?, table)? {
+                if entry.get("outcome").and_then(|o| o.as_str()) != Some("Success") {
+                    continue;
+                }
+                match entry.get("op").and_then(|o| o.as_str()) {
+                    Some("Undo") => redo.extend(undo.pop()),
+                    Some("Redo") => undo.extend(redo.pop()),
+                    _ => {
+                        undo.push((entry.clone(), prior));
+                        redo.clear();
+                    }
+                }
+            }
+            Ok((undo, redo))
+        }
+
+        /// version of table: the hash of its contents, equal to the later_id
+        /// of the last change logged for it. 0 when it doesnt exist
+        pub fn version(&self, table: &str) -> Result<u64, TErrors> {
+            match self.read_blob(table) {
+                Ok(data) => Ok(state_hash(&Format::decode::<FieldValue>(&data)?)),
+                Err(TErrors::FileNotFound) => Ok(0),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// fails with Conflict unless table is still at version
+        fn expect_version(&self, table: &str, version: u64) -> Result<(), TErrors> {
+            if self.version(table)? != version {
+                return Err(TErrors::Conflict);
+            }
+            Ok(())
+        }
+
+        /// the contents of table as JSON. None when it doesnt exist
+        fn current_state(&self, table: &str) -> Option<FieldValue> {
+            self.read_blob(table)
+                .ok()
+                .and_then(|buf| Format::decode::<FieldValue>(&buf).ok())
+        }
+
+        /// rewrites table to target, deleting it when None, and documents it as op
+        fn apply_state(
+            &self,
+            table: &str,
+            target: Option<FieldValue>,
+            op: LogOp,
+        ) -> Result<(), TErrors> {
+            let _writing = self.lock_tables(&[table])?;
+            self.write_state(table, target, op)
+        }
+
+        /// apply_state for callers already holding the gate
+        fn write_state(
+            &self,
+            table: &str,
+            target: Option<FieldValue>,
+            op: LogOp,
+        ) -> Result<(), TErrors> {
+            let _writing = self.hold_tables(&[table])?;
+            let current: Option<Vec<u8>> = self.stored_blob(table);
+            let format: Format = self.table_format(table);
+            let later: AtomicCopy = match &target {
+                None => {
+                    self.delete_blob(table)?;
+                    self.reindex_table(table, None)?;
+                    AtomicCopy::new(table.to_string(), format.ext().to_string(), Vec::new())
+                }
+                Some(state) => {
+                    let copy: AtomicCopy = self.write_ahead(&AtomicCopy::new(
+                        table.to_string(),
+                        format.ext().to_string(),
+                        format.encode(state)?,
+                    ))?;
+                    // drop the copy left behind if the table switched formats
+                    for other in Format::ALL.into_iter().filter(|f| *f != format) {
+                        self.backend.delete(&blob_name(table, other.ext()))?;
+                    }
+                    copy
+                }
+            };
+
+            log_state(self, op, table, current, Ok(later))?;
+            Ok(())
+        }
+
+        /// names of every table stored in the database.
+        /// the pages of Table<T>s are left out
+        pub fn tables(&self) -> Result<Vec<String>, TErrors> {
+            let mut tables: Vec<String> = self.stored_tables()?;
+            tables.retain(|name| !is_page(name));
+            Ok(tables)
+        }
+
+        /// tables for every file stored in a table format, the pages of Table<T>s included
+        fn stored_tables(&self) -> Result<Vec<String>, TErrors> {
+            let mut tables: Vec<String> = self
+                .backend
+                .list()?
+                .into_iter()
+                .filter_map(|f| {
+                    Format::ALL
+                        .into_iter()
+                        .find_map(|format| f.strip_suffix(&format!(".{}", format.ext())))
+                        .map(|n| n.to_string())
+                })
+                .filter(|name| name != LOG_TABLE && name != WAL_TABLE && name != MANIFEST_TABLE)
+                .collect();
+            tables.sort();
+            tables.dedup();
+            Ok(tables)
+        }
+    }
+
+    /// a logged change and the state it found its table in
+    type Change = (FieldValue, Option<FieldValue>);
+
+    /// name of a blob with the given name and extension
+    fn blob_name(name: &str, ext: &str) -> String {
+        format!("{}.{}", name, ext)
+    }
+
+    /// first bytes of every file written in Format::Binary
+    const BINARY_MAGIC: &[u8; 4] = b"CDBF";
+    /// version of the binary encoding written by this crate
+    const BINARY_VERSION: u8 = 1;
+    /// deepest nesting of arrays and objects Format::Binary decodes, the limit serde_json has too
+    const BINARY_MAX_DEPTH: usize = 128;
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    /// The encodings a table can be stored in
+    pub enum Format {
+        /// plain json, readable by hand
+        #[default]
+        Json,
+        /// compact length-prefixed encoding behind a versioned header
+        Binary,
+    }
+
+    impl Format {
+        /// every supported format
+        pub const ALL: [Format; 2] = [Format::Json, Format::Binary];
+
+        /// file extension used for the format
+        pub fn ext(&self) -> &'static str {
+            match self {
+                Format::Json => "json",
+                Format::Binary => "bin",
+            }
+        }
+
+        /// detects the format of stored bytes from their header
+        pub fn detect(data: &[u8]) -> Format {
+            if data.starts_with(BINARY_MAGIC) {
+                Format::Binary
+            } else {
+                Format::Json
+            }
+        }
+
+        /// encodes item in this format
+        pub fn encode<S: Serialize>(&self, item: &S) -> Result<Vec<u8>, TErrors> {
+            match self {
+                Format::Json => serde_json::to_vec(item).map_err(|_| {
+                    return TErrors::FormatError;
+                }),
+                Format::Binary => {
+                    let Ok(value) = serde_json::to_value(item) else {
+                        return Err(TErrors::FormatError);
+                    };
+                    let mut payload: Vec<u8> = Vec::new();
+                    encode_value(&value, &mut payload);
+
+                    let mut output: Vec<u8> = Vec::with_capacity(payload.len() + 13);
+                    output.extend_from_slice(BINARY_MAGIC);
+                    output.push(BINARY_VERSION);
+                    output.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+                    output.extend_from_slice(&payload);
+                    Ok(output)
+                }
+            }
+        }
+
+        /// decodes bytes written in any format, detected from the header
+        pub fn decode<D: DeserializeOwned>(data: &[u8]) -> Result<D, TErrors> {
+            match Format::detect(data) {
+                Format::Json => serde_json::from_slice(data).map_err(|_| {
+                    return TErrors::FormatError;
+                }),
+                Format::Binary => {
+                    let mut reader: BinaryReader = BinaryReader { data, pos: 0 };
+                    reader.take(BINARY_MAGIC.len())?;
+                    if reader.take(1)?[0] > BINARY_VERSION {
+                        println!("table written by a newer version of concept_db");
+                        return Err(TErrors::FormatError);
+                    }
+                    let len: usize = reader.u64()? as usize;
+                    if reader.data.len() - reader.pos != len {
+                        return Err(TErrors::FormatError);
+                    }
+                    let value: FieldValue = reader.value(0)?;
+                    // the payload holds exactly one value
+                    if reader.pos != reader.data.len() {
+                        return Err(TErrors::FormatError);
+                    }
+                    serde_json::from_value(value).map_err(|_| {
+                        return TErrors::FormatError;
+                    })
+                }
+            }
+        }
+    }
+
+    /// appends the binary encoding of a value to output
+    fn encode_value(value: &FieldValue, output: &mut Vec<u8>) {
+        match value {
+            FieldValue::Null => output.push(0),
+            FieldValue::Bool(false) => output.push(1),
+            FieldValue::Bool(true) => output.push(2),
+            FieldValue::Number(n) => {
+                if let Some(u) = n.as_u64() {
+                    output.push(3);
+                    encode_varint(u, output);
+                } else if let Some(i) = n.as_i64() {
+                    output.push(4);
+                    encode_varint(((i << 1) ^ (i >> 63)) as u64, output);
+                } else {
+                    output.push(5);
+                    output.extend_from_slice(&n.as_f64().unwrap_or_default().to_le_bytes());
+                }
+            }
+            FieldValue::String(s) => {
+                output.push(6);
+                encode_str(s, output);
+            }
+            FieldValue::Array(items) => {
+                output.push(7);
+                encode_varint(items.len() as u64, output);
+                items.iter().for_each(|item| encode_value(item, output));
+            }
+            FieldValue::Object(fields) => {
+                output.push(8);
+                encode_varint(fields.len() as u64, output);
+                fields.iter().for_each(|(k, v)| {
+                    encode_str(k, output);
+                    encode_value(v, output);
+                });
+            }
+        }
+    }
+
+    /// appends a length-prefixed string to output
+    fn encode_str(s: &str, output: &mut Vec<u8>) {
+        encode_varint(s.len() as u64, output);
+        output.extend_from_slice(s.as_bytes());
+    }
+
+    /// appends n to output using 7 bits per byte
+    fn encode_varint(mut n: u64, output: &mut Vec<u8>) {
+        while n >= 0x80 {
+            output.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        output.push(n as u8);
+    }
+
+    /// walks through bytes written by encode_value
+    struct BinaryReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> BinaryReader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8], TErrors> {
+            let end: usize = self.pos.saturating_add(len);
+            let Some(bytes) = self.data.get(self.pos..end) else {
+                return Err(TErrors::FormatError);
+            };
+            self.pos += len;
+            Ok(bytes)
+        }
+
+        fn varint(&mut self) -> Result<u64, TErrors> {
+            let mut n: u64 = 0;
+            for shift in (0..64).step_by(7) {
+                let byte: u8 = self.take(1)?[0];
+                n |= ((byte & 0x7f) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(n);
+                }
+            }
+            Err(TErrors::FormatError)
+        }
+
+        fn u64(&mut self) -> Result<u64, TErrors> {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(self.take(8)?);
+            Ok(u64::from_le_bytes(buf))
+        }
+
+        fn string(&mut self) -> Result<String, TErrors> {
+            let len: usize = self.varint()? as usize;
+            String::from_utf8(self.take(len)?.to_vec()).map_err(|_| {
+                return TErrors::FormatError;
+            })
+        }
+
+        /// reads a value nested depth arrays or objects deep
+        fn value(&mut self, depth: usize) -> Result<FieldValue, TErrors> {
+            let tag: u8 = self.take(1)?[0];
+            // arrays and objects
+            if matches!(tag, 7 | 8) && depth >= BINARY_MAX_DEPTH {
+                return Err(TErrors::FormatError);
+            }
+            Ok(match tag {
+                0 => FieldValue::Null,
+                1 => FieldValue::Bool(false),
+                2 => FieldValue::Bool(true),
+                3 => FieldValue::from(self.varint()?),
+                4 => {
+                    let n: u64 = self.varint()?;
+                    FieldValue::from((n >> 1) as i64 ^ -((n & 1) as i64))
+                }
+                5 => FieldValue::from(f64::from_bits(self.u64()?)),
+                6 => FieldValue::String(self.string()?),
+                7 => {
+                    let len: u64 = self.varint()?;
+                    let items = (0..len).map(|_| self.value(depth + 1));
+                    FieldValue::Array(items.collect::<Result<_, _>>()?)
+                }
+                8 => {
+                    let len: u64 = self.varint()?;
+                    let mut fields = serde_json::Map::new();
+                    for _ in 0..len {
+                        let key: String = self.string()?;
+                        fields.insert(key, self.value(depth + 1)?);
+                    }
+                    FieldValue::Object(fields)
+                }
+                _ => return Err(TErrors::FormatError),
+            })
+        }
+    }
+
+    /// name of the table the AtomicLogger documents to
+    const LOG_TABLE: &str = "logs";
+
+    /// extension of the log. one JSON entry per line
+    const LOG_EXT: &str = "jsonl";
+
+    /// name of the write-ahead log of pending writes
+    const WAL_TABLE: &str = "wal";
+
+    /// extension of the checksum stored next to every table
+    const SUM_EXT: &str = "sum";
+
+    /// name of the manifest a snapshot is described by
+    const MANIFEST_TABLE: &str = "manifest";
+
+    /// appends entries to the end of the log, one line each
+    /// every entry is given the next log id. returns the id of the last entry
+    fn append_logs(db: &Database, entries: Vec<FieldValue>) -> Result<u64, TErrors> {
+        let _log: TableGuard = db.lock_file(&blob_name(LOG_TABLE, LOG_EXT))?;
+        let seq_name: String = blob_name(LOG_TABLE, "seq");
+        let mut id: u64 = match db.backend.read(&seq_name) {
+            Ok(data) => String::from_utf8_lossy(&data)
+                .trim()
+                .parse()
+                .unwrap_or_default(),
+            Err(TErrors::FileNotFound) => 0,
+            Err(e) => return Err(e),
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        for mut entry in entries {
+            id += 1;
+            if let Some(fields) = entry.as_object_mut() {
+                fields.insert("id".to_string(), FieldValue::from(id));
+            }
+            let Ok(line) = serde_json::to_vec(&entry) else {
+                return Err(TErrors::StringConvert);
+            };
+            data.extend(line);
+            data.push(b'\n');
+        }
+        // the counter goes first so a crash can skip ids but never reuse them
+        db.backend.write(&seq_name, id.to_string().as_bytes())?;
+        db.backend.append(&blob_name(LOG_TABLE, LOG_EXT), &data)?;
+        Ok(id)
+    }
+
+    /// documents op on table without knowing its type.
+    /// prior holds the bytes stored before op, None when the table did not exist.
+    /// bytes that dont decode are hashed as they are
+    fn log_state(
+        db: &Database,
+        op: LogOp,
+        table: &str,
+        prior: Option<Vec<u8>>,
+        later: Result<AtomicCopy, TErrors>,
+    ) -> Result<u64, TErrors> {
+        append_logs(db, vec![log_entry(db, op, table, prior, later)?])
+    }
+
+    /// the entry log_state appends
+    fn log_entry(
+        db: &Database,
+        op: LogOp,
+        table: &str,
+        prior: Option<Vec<u8>>,
+        later: Result<AtomicCopy, TErrors>,
+    ) -> Result<FieldValue, TErrors> {
+        let prior_state: Option<FieldValue> = prior
+            .as_ref()
+            .and_then(|data| Format::decode::<FieldValue>(data).ok());
+
+        let mut entry: serde_json::Map<String, FieldValue> = serde_json::Map::new();
+        entry.insert("op".to_string(), serde_json::to_value(op).unwrap_or_default());
+        entry.insert("table".to_string(), FieldValue::from(table));
+        entry.insert(
+            "prior_id".to_string(),
+            FieldValue::from(stored_hash(prior.as_deref())),
+        );
+        entry.insert(
+            "later_id".to_string(),
+            FieldValue::from(later.as_ref().map(copy_hash).unwrap_or_default()),
+        );
+        entry.insert("time_stamp".to_string(), FieldValue::from(db.time_stamp()));
+        entry.insert(
+            "outcome".to_string(),
+            serde_json::to_value(Outcome::of(&later)).unwrap_or_default(),
+        );
+        entry.insert("created".to_string(), FieldValue::from(prior.is_none()));
+        let state: Option<FieldValue> = later
+            .as_ref()
+            .ok()
+            .filter(|copy| !copy.data.is_empty())
+            .and_then(|copy| Format::decode::<FieldValue>(&copy.data).ok());
+        let Ok(diff) = serde_json::to_value(Diff::states(prior_state.as_ref(), state.as_ref()))
+        else {
+            return Err(TErrors::StringConvert);
+        };
+        entry.insert("diff".to_string(), diff);
+        let Ok(later) = serialize_later(&later, serde_json::value::Serializer) else {
+            return Err(TErrors::StringConvert);
+        };
+        entry.insert("later".to_string(), later);
+        Ok(FieldValue::Object(entry))
+    }
+
+    #[derive(Serialize, Deserialize)]
+    /// an AtomicCopy as the log keeps it, holding the state written instead of its bytes.
+    /// entries logged before states were kept hold the bytes in data
+    struct LoggedCopy {
+        title: String,
+        ext: String,
+        /// the state written. None when the table was deleted
+        #[serde(default)]
+        state: Option<FieldValue>,
+        #[serde(default, skip_serializing)]
+        data: Vec<u8>,
+    }
+
+    impl LoggedCopy {
+        /// the state written, decoding the bytes of older entries
+        fn state(&self) -> Result<Option<FieldValue>, TErrors> {
+            if self.state.is_some() || self.data.is_empty() {
+                return Ok(self.state.clone());
+            }
+            Ok(Some(Format::decode::<FieldValue>(&self.data)?))
+        }
+    }
+
+    impl From<&AtomicCopy> for LoggedCopy {
+        fn from(copy: &AtomicCopy) -> Self {
+            Self {
+                title: copy.title.clone(),
+                ext: copy.ext.clone(),
+                state: Format::decode::<FieldValue>(&copy.data).ok(),
+                data: Vec::new(),
+            }
+        }
+    }
+
+    /// writes the later copy of a log entry as a LoggedCopy
+    fn serialize_later<S: serde::Serializer>(
+        later: &Result<AtomicCopy, TErrors>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        later
+            .as_ref()
+            .map(LoggedCopy::from)
+            .map_err(Clone::clone)
+            .serialize(serializer)
+    }
+
+    /// reads the later copy of a log entry back, encoding its state in the table's format
+    fn deserialize_later<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Result<AtomicCopy, TErrors>, D::Error> {
+        let logged: Result<LoggedCopy, TErrors> = Deserialize::deserialize(deserializer)?;
+        Ok(logged.and_then(|logged| {
+            let format: Format = Format::ALL
+                .into_iter()
+                .find(|f| f.ext() == logged.ext)
+                .unwrap_or_default();
+            let data: Vec<u8> = match logged.state()? {
+                Some(state) => format.encode(&state)?,
+                None => Vec::new(),
+            };
+            Ok(AtomicCopy::new(logged.title, logged.ext, data))
+        }))
+    }
+
+    /// the state of the table after the logged entry. None when it was deleted
+    fn state_after(entry: &FieldValue) -> Result<Option<FieldValue>, TErrors> {
+        let Some(later) = entry
+            .get("later")
+            .and_then(|l| l.get("Ok"))
+            .and_then(|l| serde_json::from_value::<LoggedCopy>(l.clone()).ok())
+        else {
+            return Err(TErrors::FormatError);
+        };
+        later.state()
+    }
+
+    /// every entry of table in entries with the state it found the table in, oldest first.
+    /// that is the state the last successful entry before it left behind,
+    /// or its own diff reverted when nothing earlier was logged
+    fn history<'a>(
+        entries: &'a [FieldValue],
+        table: &str,
+    ) -> Result<Vec<(&'a FieldValue, Option<FieldValue>)>, TErrors> {
+        let mut history: Vec<(&FieldValue, Option<FieldValue>)> = Vec::new();
+        let mut last: Option<Option<FieldValue>> = None;
+
+        for entry in entries {
+            if entry.get("table").and_then(|t| t.as_str()) != Some(table) {
+                continue;
+            }
+            let success: bool = entry.get("outcome").and_then(|o| o.as_str()) == Some("Success");
+            let created: bool = entry.get("created") == Some(&FieldValue::Bool(true));
+            let prior: Option<FieldValue> = if created {
+                None
+            } else if let Some(last) = &last {
+                last.clone()
+            } else if success {
+                let diff: Diff = entry
+                    .get("diff")
+                    .and_then(|d| serde_json::from_value::<Diff>(d.clone()).ok())
+                    .unwrap_or_default();
+                Some(diff.revert(state_after(entry)?))
+            } else {
+                None
+            };
+
+            if success {
+                last = Some(state_after(entry)?);
+            }
+            history.push((entry, prior));
+        }
+        Ok(history)
+    }
+
+    /// hash of a table state, independent of the type it was stored as
+    pub fn state_hash<S: Serialize>(state: &S) -> u64 {
+        let canonical: FieldValue = serde_json::to_value(state).unwrap_or_default();
+        checksum(&serde_json::to_vec(&canonical).unwrap_or_default())
+    }
+
+    /// hash of the state stored as data. bytes that dont decode are hashed as they are,
+    /// 0 when nothing is stored
+    fn stored_hash(data: Option<&[u8]>) -> u64 {
+        match data.map(|data| (Format::decode::<FieldValue>(data), data)) {
+            Some((Ok(state), _)) => state_hash(&state),
+            Some((Err(_), data)) => checksum(data),
+            None => 0,
+        }
+    }
+
+    /// hash of the state written by copy. 0 when it holds none
+    fn copy_hash(copy: &AtomicCopy) -> u64 {
+        if copy.data.is_empty() {
+            return 0;
+        }
+        Format::decode::<FieldValue>(&copy.data)
+            .map(|state| state_hash(&state))
+            .unwrap_or_else(|_| write_hash(copy.clone()))
+    }
+
+    pub fn write_hash<T>(item: T) -> u64
+    where
+        T: Hash,
+    {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// An output design for the Logger
+    pub struct Commit<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
+        pub success: bool,
+        pub package: Result<T, TErrors>,
+        pub collection: Result<Collection<T>, TErrors>,
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Default
+        for Commit<T>
+    {
+        fn default() -> Self {
+            Self {
+                success: false,
+                package: Err(TErrors::default()),
+                collection: Err(TErrors::default()),
+            }
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Commit<T> {
+        pub fn determine(
+            &self,
+            package: Result<T, TErrors>,
+            collection: Result<Collection<T>, TErrors>,
+        ) -> Self {
+            let success: bool = !(package.is_err() && collection.is_err());
+
+            Self {
+                success,
+                package,
+                collection,
+            }
+        }
+    }
+
+    pub trait Collect<T: Serialize + DeserializeOwned + Sized + Clone + Debug> {
+        /// creates new instance
+        fn new(inner: Vec<T>) -> Self;
+        /// collects all tables across the JSON files that match type of T.
+        fn collect(&self, frag: Fragment<T>) -> Result<Self, TErrors>
+        where
+            Self: Sized;
+        /// adds a table of type T to collection.
+        fn append(&mut self, obj: T) -> Self;
+        /// removes an object of type T from the collection.
+        fn remove(&self, obj: T) -> Self
+        where
+            T: PartialEq;
+        /// update an index to the provided object of type T.
+        fn update_index(&self, index: usize, new_obj: T) -> Self;
+        /// returns the object whose primary key matches key.
+        fn get_by_key<K: PartialEq>(&self, key: &K) -> Option<T>
+        where
+            T: Keyed<K>;
+        /// removes every object whose primary key matches key.
+        fn remove_by_key<K: PartialEq>(&self, key: &K) -> Self
+        where
+            T: Keyed<K>;
+        /// replaces the object sharing new_obj's primary key with new_obj.
+        fn update_by_key<K: PartialEq>(&self, new_obj: T) -> Self
+        where
+            T: Keyed<K>;
+        /// keeps only the first object for every primary key.
+        fn dedupe_by_key<K: Eq + Hash>(&self) -> Self
+        where
+            T: Keyed<K>;
+        /// write collection to json file.  
+        fn write_to_file(&self, db: &Database, title: String) -> Result<(), TErrors>
+        where
+            Self: Serialize + DeserializeOwned + Clone + Debug;
+    }
+
+    /// gives a record its identity
+    pub trait Keyed<K> {
+        /// primary key of the record
+        fn key(&self) -> K;
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    /// how generated primary keys are produced
+    pub enum KeyStrategy {
+        /// 1, 2, 3... counted per table
+        AutoIncrement,
+        /// random UUIDv4 style key
+        Uuid,
+    }
+
+    /// opt in for records whose primary key is generated on insert
+    pub trait GeneratedKey {
+        /// how the key is generated
+        fn strategy() -> KeyStrategy;
+        /// stores the generated key on the record
+        fn set_key(&mut self, key: String);
+    }
+
+    /// creates a random UUIDv4 style key
+    pub fn generate_uuid() -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let nanos: u128 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let counter: u64 = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let mut halves = [0u64; 2];
+        for (i, half) in halves.iter_mut().enumerate() {
+            *half = RandomState::new().hash_one((nanos, counter, i, std::process::id()));
+        }
+
+        let bytes: u128 = ((halves[0] as u128) << 64 | halves[1] as u128)
+            & !(0xf << 76)
+            & !(0x3 << 62)
+            | (0x4 << 76)
+            | (0x2 << 62);
+        let hex: String = format!("{:032x}", bytes);
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+
+    /// value of a single field of T. covers strings, numbers, bools, null,
+    /// arrays and nested objects.
+    pub type FieldValue = serde_json::Value;
+
+    /// covers the different kinds of HashMap that may be required for conversion
+    /// from intended struct.
+    pub trait ToHash {
+        /// converts T to HashMap.
+        fn to_hash(&self) -> Result<HashMap<String, FieldValue>, TErrors>;
+        /// converts T to HashMap of String and Option of FieldValue.
+        fn to_hash_opt(&self) -> Result<HashMap<String, Option<FieldValue>>, TErrors>;
+        /// converts T to HashMap of String and Vec of FieldValue.
+        fn to_hash_vec(&self) -> Result<HashMap<String, Vec<FieldValue>>, TErrors>;
+        /// converts hashmap to Vec of tuple of String and FieldValue.
+        fn zip(&self) -> Result<Vec<(String, FieldValue)>, TErrors>;
+    }
+
+    pub trait ToLogAtomic<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
+        /// create new Atomic Logger instance 
+        fn new(prior: T, later: AtomicCopy, time_stamp: String) -> Self; 
+        /// write ids based on hash of prior and later
+        fn set_hash_ids(&self) -> Result<Self, TErrors>
+        where
+            Self: Sized;
+        /// compares two hashes from original and altered states
+        fn compare_ids(&self) -> bool;
+        /// updates initial state
+        fn set_prior(&self, prior: T) -> Self;
+        /// intended to set updated state on completion for comparision
+        fn set_later(&self, later: AtomicCopy) -> Self;
+        /// write changes to logger
+        fn document(&self, db: &Database) -> Result<(), TErrors>;
+        /// sets the time at which change occured.
+        fn set_time_stamp(&self, time_stamp: String) -> Self;
+        /// fields added, removed and changed between the prior and later states
+        fn diff(&self) -> Result<Diff, TErrors>;
+        /// measures success of execution
+        fn commit(&self) -> Result<Commit<AtomicCopy>, TErrors>;
+        /// returns true if successful operation
+        fn is_success(&self) -> Result<bool, TErrors>;
+        /// returns true if failed operation
+        fn is_failure(&self) -> Result<bool, TErrors>;
+        /// rewrites the table from prior through a temp file and verifies the result.
+        /// a table created by the logged write is deleted instead.
+        /// the rollback is documented itself and its entry returned.
+        fn rollback(&self, db: &Database) -> Result<AtomicLogger<T>, TErrors>;
+    }
+
+    /// simplifies code in ToHash trait
+    // why did I even make this??
+    trait ToHashOpt {
+        /// converts T to HashMap of String and Option of FieldValue.
+        fn convert_opt(&self) -> HashMap<String, Option<FieldValue>>;
+    }
+
+    impl ToHashOpt for HashMap<String, FieldValue> {
+        /// impl of ToHashOpt.convert_opt()
+        fn convert_opt(&self) -> HashMap<String, Option<FieldValue>> {
+            let mut temp_hash: HashMap<String, Option<FieldValue>> = HashMap::new();
+
+            self.iter().for_each(|(k, v)| {
+                let v: Option<FieldValue> = if !v.is_null() { Some(v.clone()) } else { None };
+
+                temp_hash.insert(k.clone(), v);
+            });
+
+            temp_hash
+        }
+    }
+
+    /// converts any serializable value into a map of its fields
+    fn value_to_hash<S: Serialize>(item: &S) -> Result<HashMap<String, FieldValue>, TErrors> {
+        let Ok(FieldValue::Object(fields)) = serde_json::to_value(item) else {
+            return Err(TErrors::HashConvert);
+        };
+
+        Ok(fields.into_iter().collect())
+    }
+
+    /// compares a stored field against a wanted value.
+    /// strings are compared after trimming.
+    fn values_match(stored: &FieldValue, wanted: &FieldValue) -> bool {
+        match (stored, wanted) {
+            (FieldValue::String(a), FieldValue::String(b)) => a.trim() == b.trim(),
+            _ => stored == wanted,
+        }
+    }
+
+    /// added functionality to count iterations of an item in a vector
+    /// of a generic type
+    pub fn count_val<T: std::fmt::Debug + Eq>(vec: Vec<T>, value: String) -> usize {
+        vec.into_iter()
+            .map(|c| format!("{c:?}").trim().to_string())
+            .filter(|v| v.as_str() == value.trim())
+            .collect::<Vec<String>>()
+            .len()
+    }
+
+    #[derive(Debug, Serialize)]
+    /// Takes in a struct of type T,
+    /// then applies concept_db functionality
+    pub struct Fragment<T: Serialize + DeserializeOwned + Sized + Clone> {
+        pub inner: T,
+        #[serde(skip)]
+        db: Database,
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug> Display for Fragment<T> {
+        /// converts T to String
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            let Ok(output) = to_string(&self.inner) else {
+                panic!("unable to convert: {self:?}");
+            };
+
+            f.write_str(&output)
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug> ToHash for Fragment<T> {
+        /// converts T to HashMap<String, FieldValue>
+        fn to_hash(&self) -> Result<HashMap<String, FieldValue>, TErrors> {
+            value_to_hash(&self.inner)
+        }
+        /// converts T to HashMap<String, Option<FieldValue>>
+        fn to_hash_opt(&self) -> Result<HashMap<String, Option<FieldValue>>, TErrors> {
+            let Ok(output) = self.to_hash() else {
+                return Err(TErrors::HashConvert);
+            };
+
+            Ok(output.convert_opt())
+        }
+
+        /// converts T to HashMap<String, Vec<FieldValue>>
+        fn to_hash_vec(&self) -> Result<HashMap<String, Vec<FieldValue>>, TErrors> {
+            let Ok(output) =
+                serde_json::from_str::<HashMap<String, Vec<FieldValue>>>(&self.to_string())
+            else {
+                return Err(TErrors::StringConvert);
+            };
+
+            Ok(output)
+        }
+
+        fn zip(&self) -> Result<Vec<(String, FieldValue)>, TErrors> {
+            let mut _temp_vec: Vec<(String, FieldValue)> = Vec::new();
+            if let Ok(vec_hash) = self.to_hash() {
+                let keys = vec_hash.clone().into_keys().collect::<Vec<String>>();
+                let values = vec_hash.into_values().collect::<Vec<FieldValue>>();
+                _temp_vec = keys.iter().cloned().zip(values).collect();
+                return Ok(_temp_vec);
+            } else {
+                println!("This is an error still in work. Try raw_changes_collect.");
+                return Err(TErrors::HashConvert);
+            }
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Fragment<T> {
+        /// initializes the Fragment<T> against the default database
+        pub fn new(inner: T) -> Self
+        where
+            T: Serialize + DeserializeOwned + Sized + Clone,
+        {
+            Self {
+                inner,
+                db: Database::default(),
+            }
+        }
+
+        /// returns the database this Fragment<T> operates on
+        pub fn db(&self) -> &Database {
+            &self.db
+        }
+
+        /// points the Fragment<T> at another database
+        pub fn set_db(&self, db: Database) -> Self {
+            Self {
+                inner: self.inner.clone(),
+                db,
+            }
+        }
+
+        /// parses file if exists then returns a new Fragment<T>
+        pub fn read_table(&self, file_path: String) -> Result<Fragment<T>, TErrors> {
+            let contents: Vec<u8> = match self.db.read_blob(&file_path) {
+                Ok(contents) => contents,
+                Err(TErrors::FileNotFound) => return Err(TErrors::FileNotFound),
+                Err(TErrors::CheckError) => return Err(TErrors::CheckError),
+                Err(TErrors::Locked) => return Err(TErrors::Locked),
+                Err(_) => {
+                    println!("Failed to read file under 'read_table'");
+                    return Err(TErrors::FileError);
+                }
+            };
+
+            let Ok(inner_value) = Format::decode(&contents) else {
+                return Err(TErrors::ReadByteError);
+            };
+
+            let new_value: Fragment<T> = self.db.fragment(inner_value);
+
+            Ok(new_value)
+        }
+
+        /// creates a new file in the table's format and inputs the table
+        /// created using the struct
+        pub fn create_table(&self, table_name: String) -> Result<&Self, TErrors> {
+            let _writing = self.db.lock_tables(&[&table_name])?;
+            let format: Format = self.db.table_format(&table_name);
+
+            let atom: AtomicCopy = AtomicCopy::new(
+                table_name.clone(),
+                format.ext().to_string(),
+                format.encode(&self.inner)?,
+            );
+
+            // read past the checksum so a corrupt table isnt logged as created
+            let stored: Option<Vec<u8>> = self.db.stored_blob(&table_name);
+            let existing: Option<T> = stored
+                .as_ref()
+                .and_then(|buf| Format::decode::<T>(buf).ok());
+
+            let later: Result<AtomicCopy, TErrors> = self.db.write_ahead(&atom);
+            let atomic_logger = AtomicLogger {
+                id: 0,
+                op: LogOp::Write,
+                table: table_name.clone(),
+                prior_id: stored_hash(stored.as_deref()),
+                later_id: state_hash(&self.inner),
+                time_stamp: self.db.time_stamp(),
+                outcome: Outcome::of(&later),
+                created: stored.is_none(),
+                diff: Diff::between(existing.as_ref(), Some(&self.inner)),
+                prior: existing.unwrap_or_default(),
+                later, 
+            }; 
+
+            atomic_logger.document(&self.db)?; 
+            atomic_logger.later?;
+
+            // drop the copy left behind if the table switched formats
+            for other in Format::ALL.into_iter().filter(|f| *f != format) {
+                self.db.backend.delete(&blob_name(&table_name, other.ext()))?;
+            }
+
+            Ok(self)
+        }
+
+        /// deletes the table in question
+        pub fn delete_table(&self, table_name: String) -> Result<(), TErrors> {
+            let _writing = self.db.lock_tables(&[&table_name])?;
+            let stored: Option<Vec<u8>> = self.db.stored_blob(&table_name);
+            let ext: &str = self.db.table_format(&table_name).ext();
+
+            let deleted: Result<AtomicCopy, TErrors> = self
+                .db
+                .delete_blob(&table_name)
+                .map(|_| AtomicCopy::new(table_name.clone(), ext.to_string(), Vec::new()));
+
+            if let Some(stored) = stored {
+                let existing: Option<T> = Format::decode::<T>(&stored).ok();
+                AtomicLogger {
+                    id: 0,
+                    op: LogOp::Delete,
+                    table: table_name.clone(),
+                    prior_id: stored_hash(Some(&stored)),
+                    later_id: 0,
+                    time_stamp: self.db.time_stamp(),
+                    outcome: Outcome::of(&deleted),
+                    created: false,
+                    diff: Diff::between(existing.as_ref(), None::<&T>),
+                    prior: existing.unwrap_or_default(),
+                    later: deleted.clone(),
+                }
+                .document(&self.db)?;
+            }
+
+            deleted?;
+            self.db.reindex_table(&table_name, None)
+        }
+        /// deletes table if tables contents match T of Fragment<T>
+        pub fn delete_table_infer(&self) -> Result<(), TErrors> {
+            for table in self.db.tables()? {
+                if self.read_table(table.clone()).is_ok() {
+                    self.delete_table(table)?;
+                } else {
+                    continue;
+                }
+            }
+            Ok(())
+        }
+
+        /// returns all regardless of type of T
+        pub fn get_all(&self) -> Result<Vec<HashMap<String, FieldValue>>, TErrors> {
+            let mut temp_vec: Vec<HashMap<String, FieldValue>> = Vec::new();
+            for table in self.db.tables()? {
+                let Ok(contents) = self.db.read_blob(&table) else {
+                    return Err(TErrors::ReadByteError);
+                };
+
+                let Ok(obj_hash) = Format::decode::<HashMap<String, FieldValue>>(&contents) else {
+                    return Err(TErrors::HashConvert);
+                };
+                temp_vec.push(obj_hash);
+            }
+            Ok(temp_vec)
+        }
+
+        /// returns only matching initial type of T
+        pub fn get_all_infer(&self) -> Result<Vec<Fragment<T>>, TErrors> {
+            let mut temp_vec: Vec<Fragment<T>> = Vec::new();
+
+            for table in self.db.tables()? {
+                if let Ok(obj) = self.read_table(table) {
+                    temp_vec.push(obj);
+                } else {
+                    continue;
+                }
+            }
+
+            Ok(temp_vec)
+        }
+
+        /// returns the name and contents of the table whose record has the primary key
+        pub fn get_by_key<K: PartialEq>(&self, key: &K) -> Result<Option<(String, Fragment<T>)>, TErrors>
+        where
+            T: Keyed<K>,
+        {
+            for table in self.db.tables()? {
+                let Ok(obj) = self.read_table(table.clone()) else {
+                    continue;
+                };
+                if obj.inner.key() == *key {
+                    return Ok(Some((table, obj)));
+                }
+            }
+
+            Ok(None)
+        }
+
+        /// overwrites the table holding the record with the same primary key as self.
+        /// returns the name of the table written
+        pub fn update_by_key<K: PartialEq>(&self) -> Result<String, TErrors>
+        where
+            T: Keyed<K>,
+        {
+            let Some((table, _)) = self.get_by_key(&self.inner.key())? else {
+                return Err(TErrors::FileNotFound);
+            };
+
+            self.create_table(table.clone())?;
+
+            Ok(table)
+        }
+
+        /// deletes every table of type T whose primary key was already seen
+        /// in an earlier table. returns the names of the deleted tables
+        pub fn dedupe_by_key<K: Eq + Hash>(&self) -> Result<Vec<String>, TErrors>
+        where
+            T: Keyed<K>,
+        {
+            let mut seen: HashSet<K> = HashSet::new();
+            let mut deleted: Vec<String> = Vec::new();
+
+            for table in self.db.tables()? {
+                let Ok(obj) = self.read_table(table.clone()) else {
+                    continue;
+                };
+                if !seen.insert(obj.inner.key()) {
+                    self.delete_table(table.clone())?;
+                    deleted.push(table);
+                }
+            }
+
+            Ok(deleted)
+        }
+
+        /// combines 2 tables
+        pub fn merge(
+            &self,
+            foreign_table: HashMap<String, FieldValue>,
+        ) -> Result<HashMap<String, FieldValue>, TErrors> {
+            let Ok(mut hashed_self) = self.to_hash() else {
+                return Err(TErrors::HashConvert);
+            };
+            for (key, value) in foreign_table {
+                hashed_self.insert(key, value);
+            }
+
+            Ok(hashed_self)
+        }
+
+        /// performs a left join on 2 tables.
+        /// columns found on both sides are renamed 'left.column' and 'right.column'
+        pub fn left_join(
+            &self,
+            foreign_table: HashMap<String, Option<FieldValue>>,
+        ) -> Result<HashMap<String, FieldValue>, TErrors> {
+            let Ok(self_hashed) = self.to_hash() else {
+                return Err(TErrors::HashConvert);
+            };
+
+            let foreign: HashMap<String, FieldValue> = foreign_table
+                .into_iter()
+                .map(|(k, v)| (k, v.unwrap_or(FieldValue::Null)))
+                .collect();
+
+            let conflicts: HashSet<String> = self_hashed
+                .keys()
+                .filter(|k| foreign.contains_key(*k))
+                .cloned()
+                .collect();
+
+            Ok(merge_rows(
+                ("left", Some(self_hashed)),
+                ("right", Some(foreign)),
+                &conflicts,
+                None,
+            ))
+        }
+
+
+        /// sorts tables based on key + value.
+        /// shorthand for a Query with a single eq condition
+        pub fn build_where(&self, key: String, value: FieldValue) -> Result<Vec<Self>, TErrors> {
+            self.query(&Query::new().eq(&key, value))
+        }
+
+        /// runs query against every table of type T
+        pub fn query(&self, query: &Query<T>) -> Result<Vec<Self>, TErrors> {
+            query.run(&self.db)
+        }
+
+        /// update key in table then writes it back
+        pub fn update_table(
+            &self,
+            table_name: String,
+            key: String,
+            value: FieldValue,
+        ) -> Result<T, TErrors> {
+            let _writing = self.db.lock_tables(&[&table_name])?;
+            let Ok(current_table) = self.read_table(table_name.clone()) else {
+                return Err(TErrors::FileError);
+            };
+
+            let Ok(mut hashed_table) = current_table.to_hash() else {
+                return Err(TErrors::HashConvert);
+            };
+
+            hashed_table.insert(key, value);
+
+            let Ok(output) =
+                serde_json::from_value::<T>(FieldValue::Object(hashed_table.into_iter().collect()))
+            else {
+                return Err(TErrors::StringConvert);
+            };
+
+            self.db.fragment(output.clone()).create_table(table_name)?;
+
+            Ok(output)
+        }
+
+        /// updates table's key and value if value is type of Vec<FieldValue> then writes it back
+        pub fn update_table_vec(
+            &self,
+            table_name: String,
+            key: String,
+            value: Vec<FieldValue>,
+        ) -> Result<T, TErrors> {
+            let _writing = self.db.lock_tables(&[&table_name])?;
+            let Ok(current_table) = self.read_table(table_name.clone()) else {
+                return Err(TErrors::FileError);
+            };
+
+            let Ok(mut hashed_table) = current_table.to_hash() else {
+                return Err(TErrors::HashConvert);
+            };
+
+            hashed_table.insert(key, FieldValue::Array(value));
+
+            let Ok(output) =
+                serde_json::from_value::<T>(FieldValue::Object(hashed_table.into_iter().collect()))
+            else {
+                return Err(TErrors::StringConvert);
+            };
+
+            self.db.fragment(output.clone()).create_table(table_name)?;
+
+            Ok(output)
+        }
+
+        /// update_table, failing with Conflict if the table is no longer at version.
+        /// read the version with Database.version when the table is read
+        pub fn update_table_if(
+            &self,
+            table_name: String,
+            key: String,
+            value: FieldValue,
+            version: u64,
+        ) -> Result<T, TErrors> {
+            let _writing = self.db.lock_tables(&[&table_name])?;
+            self.db.expect_version(&table_name, version)?;
+            self.update_table(table_name, key, value)
+        }
+
+        /// create_table, failing with Conflict if the table is no longer at version.
+        /// version 0 only creates a table that doesnt exist yet
+        pub fn create_table_if(&self, table_name: String, version: u64) -> Result<&Self, TErrors> {
+            let _writing = self.db.lock_tables(&[&table_name])?;
+            self.db.expect_version(&table_name, version)?;
+            self.create_table(table_name)
+        }
+    }
+
+    #[derive(Serialize, Deserialize,Clone, Debug, Hash)]
+    /// Designed to apply actions to temp file before permanent.
+    /// permanent file then only changed on successful execution.
+    pub struct AtomicCopy {
+        pub title: String,
+        pub ext: String,
+        pub data: Vec<u8>,
+        /// name of the temp file, given out by construct
+        #[serde(default)]
+        pub temp: String,
+    }
+
+    impl Default for AtomicCopy {
+        fn default() -> Self {
+            Self {
+                title: "example".to_string(),
+                ext: "json".to_string(),
+                data: Vec::new(),
+                temp: String::new(),
+            }
+        }
+    }
+
+    impl AtomicCopy {
+        /// creates new instance of Atomic Copy
+        pub fn new(title: String, ext: String, data: Vec<u8>) -> Self {
+            Self {
+                title,
+                ext,
+                data,
+                temp: String::new(),
+            }
+        }
+        /// sets file title
+        pub fn set_title(&self, title: String) -> Self {
+            Self {
+                title,
+                ..self.clone()
+            }
+        }
+        /// sets file extension
+        pub fn set_ext(&self, ext: String) -> Self {
+            Self {
+                title: self.title.clone(),
+                ext,
+                data: self.data.clone(),
+                temp: self.temp.clone(),
+            }
+        }
+        /// sets the name of the temp file
+        pub fn set_temp(&self, temp: String) -> Self {
+            Self {
+                temp,
+                ..self.clone()
+            }
+        }
+        /// name of the temp file. copies never constructed use '{title}.temp'
+        pub fn temp_name(&self) -> String {
+            if self.temp.is_empty() {
+                return blob_name(&self.title, "temp");
+            }
+            self.temp.clone()
+        }
+        /// creates file and file contents.
+        /// a copy without a temp name is given a unique one first
+        pub fn construct(&self, db: &Database) -> Result<Self, TErrors> {
+            let copy: AtomicCopy = if self.temp.is_empty() {
+                self.set_temp(unique_temp(&self.title))
+            } else {
+                self.clone()
+            };
+            db.backend.write(&copy.temp, &copy.data).map_err(|e| {
+                println!("failed to create file under 'construct'");
+                return e;
+            })?;
+
+            Ok(copy)
+        }
+        /// replaces temp with permanent file
+        pub fn replace(&self, db: &Database) -> Result<Self, TErrors> {
+            db.backend
+                .rename(&self.temp_name(), &blob_name(&self.title, &self.ext))?;
+            Ok(self.clone())
+        }
+        /// checks whether content is same between temp and permanent file.
+        /// once the temp file has been replaced, the permanent file is checked against data
+        pub fn check(&self, db: &Database) -> Result<Self, TErrors> {
+            let temp_name: String = self.temp_name();
+            let temp_data = if db.backend.exists(&temp_name) {
+                db.backend.read(&temp_name).map_err(|_| {
+                    return TErrors::ReadByteError;
+                })?
+            } else {
+                self.data.clone()
+            };
+
+            let current_data = db.backend.read(&blob_name(&self.title, &self.ext))
+                .map_err(|_| {
+                    return TErrors::ReadByteError;
+                })?;
+
+            if temp_data != current_data {
+                return Err(TErrors::CheckError);
+            }
+
+            Ok(self.clone())
+        }
+        /// deletes the temp file
+        pub fn destroy(&self, db: &Database) -> Result<(), TErrors> {
+            db.backend.delete(&self.temp_name()).map_err(|_| {
+                println!("Failed to delete temp file.");
+                return TErrors::FileError;
+            })
+        }
+    }
+
+    /// unique name of a temp file of title: '{title}.{writer id}-{counter}.temp'.
+    /// the writer id is the process id, the counter is shared by every thread
+    fn unique_temp(title: &str) -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let counter: u64 = COUNTER.fetch_add(1, Ordering::Relaxed);
+        blob_name(title, &format!("{}-{}.temp", process::id(), counter))
+    }
+
+    #[derive(Debug, Clone)]
+    /// a table write waiting in a Transaction
+    struct Staged {
+        copy: AtomicCopy,
+        format: Format,
+        record: Option<FieldValue>,
+    }
+
+    #[derive(Debug)]
+    /// Stages writes to several tables as temp files,
+    /// then commits all of them or none.
+    /// dropping it without a commit aborts it
+    pub struct Transaction {
+        db: Database,
+        staged: Vec<Staged>,
+    }
+
+    impl Transaction {
+        /// creates new instance
+        pub fn new(db: Database) -> Self {
+            Self {
+                db,
+                staged: Vec::new(),
+            }
+        }
+
+        /// names of the tables staged so far
+        pub fn tables(&self) -> Vec<String> {
+            self.staged.iter().map(|s| s.copy.title.clone()).collect()
+        }
+
+        /// writes inner to the temp file of table.
+        /// staging the same table twice keeps the latest write
+        pub fn stage<T>(&mut self, table: String, inner: &T) -> Result<&mut Self, TErrors>
+        where
+            T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default,
+        {
+            self.db.writable()?;
+            check_table_name(&table)?;
+            let format: Format = self.db.table_format(&table);
+            let copy: AtomicCopy = AtomicCopy::new(
+                table.clone(),
+                format.ext().to_string(),
+                format.encode(inner)?,
+            )
+            .construct(&self.db)?;
+
+            if let Some(i) = self.staged.iter().position(|s| s.copy.title == table) {
+                let replaced: Staged = self.staged.remove(i);
+                self.discard(&replaced)?;
+            }
+            self.db.hold_temp(copy.temp_name())?;
+            self.staged.push(Staged {
+                copy,
+                format,
+                record: serde_json::to_value(inner).ok(),
+            });
+            Ok(self)
+        }
+
+        /// replaces every permanent file with its staged temp file.
+        /// if any replace fails the tables already replaced are restored
+        /// and the remaining temp files are deleted.
+        pub fn commit(mut self) -> Result<(), TErrors> {
+            let staged: Vec<Staged> = std::mem::take(&mut self.staged);
+            let committed: Result<(), TErrors> = self.replace_all(&staged);
+            for staged in &staged {
+                if committed.is_err() {
+                    staged.copy.destroy(&self.db)?;
+                }
+                self.db.release_temp(&staged.copy.temp_name())?;
+            }
+            committed
+        }
+
+        /// commit of staged, whose temp files the caller forgets afterwards
+        fn replace_all(&self, staged: &[Staged]) -> Result<(), TErrors> {
+            let titles: Vec<&str> = staged.iter().map(|s| s.copy.title.as_str()).collect();
+            let _writing = self.db.lock_tables(&titles)?;
+            // read under the locks, so a write made since staging is what gets logged
+            let priors: Vec<Option<Vec<u8>>> = titles
+                .iter()
+                .map(|title| self.db.stored_blob(title))
+                .collect();
+            let copies: Vec<AtomicCopy> = staged.iter().map(|s| s.copy.clone()).collect();
+            // every temp file is already written, recovery may finish the commit
+            let wal_id: u64 = self.db.wal_open(WalState::Prepared, &copies, &[])?;
+            let mut replaced: Vec<(&Staged, Option<Vec<u8>>)> = Vec::new();
+
+            for (i, pending) in staged.iter().enumerate() {
+                let permanent: String = blob_name(&pending.copy.title, &pending.copy.ext);
+                let prior: Option<Vec<u8>> = self.db.backend.read(&permanent).ok();
+
+                let outcome: Result<AtomicCopy, TErrors> = match pending.copy.replace(&self.db) {
+                    Ok(copy) => {
+                        replaced.push((pending, prior));
+                        self.db
+                            .write_sum(&copy.title, &copy.data)
+                            .and_then(|_| copy.check(&self.db))
+                    }
+                    Err(e) => Err(e),
+                };
+
+                if let Err(e) = outcome {
+                    for (staged, prior) in replaced {
+                        let title: &str = &staged.copy.title;
+                        let ext: &str = &staged.copy.ext;
+                        match prior {
+                            Some(data) => {
+                                self.db.write_sum(title, &data)?;
+                                AtomicCopy::new(title.to_string(), ext.to_string(), data)
+                                    .construct(&self.db)?
+                                    .replace(&self.db)?;
+                            }
+                            None => {
+                                self.db.backend.delete(&blob_name(title, ext))?;
+                                self.db.backend.delete(&blob_name(title, SUM_EXT))?;
+                            }
+                        }
+                    }
+                    for remaining in &staged[i..] {
+                        remaining.copy.destroy(&self.db)?;
+                    }
+                    self.db.wal_close(wal_id)?;
+                    return Err(e);
+                }
+            }
+            // before the entry closes, so recover redoes an update cut short
+            for staged in staged {
+                self.db
+                    .reindex_table(&staged.copy.title, staged.record.as_ref())?;
+            }
+            self.db.wal_close(wal_id)?;
+
+            for staged in staged {
+                let table: &str = &staged.copy.title;
+                // drop the copy left behind if the table switched formats
+                for other in Format::ALL.into_iter().filter(|f| *f != staged.format) {
+                    self.db.backend.delete(&blob_name(table, other.ext()))?;
+                }
+            }
+
+            let mut entries: Vec<FieldValue> = Vec::new();
+            for (staged, prior) in staged.iter().zip(priors) {
+                let (table, later) = (&staged.copy.title, Ok(staged.copy.clone()));
+                entries.push(log_entry(&self.db, LogOp::Write, table, prior, later)?);
+            }
+            append_logs(&self.db, entries)?;
+            Ok(())
+        }
+
+        /// deletes every staged temp file, leaving the tables untouched
+        pub fn abort(mut self) -> Result<(), TErrors> {
+            for staged in std::mem::take(&mut self.staged) {
+                self.discard(&staged)?;
+            }
+            Ok(())
+        }
+
+        /// deletes the temp file of staged and lets repair see it again
+        fn discard(&self, staged: &Staged) -> Result<(), TErrors> {
+            staged.copy.destroy(&self.db)?;
+            self.db.release_temp(&staged.copy.temp_name())
+        }
+    }
+
+    impl Drop for Transaction {
+        fn drop(&mut self) {
+            for staged in std::mem::take(&mut self.staged) {
+                let _ = self.discard(&staged);
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    /// how far a write got before it was logged to the write-ahead log
+    enum WalState {
+        /// temp files may be partially written, recovery discards them
+        Begin,
+        /// every temp file is written, recovery replaces the permanent files with them
+        Prepared,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    /// a pending write in the write-ahead log
+    struct WalEntry {
+        id: u64,
+        state: WalState,
+        /// title and extension of every file written
+        targets: Vec<(String, String)>,
+        /// temp file of every target, in the same order.
+        /// missing for entries written before temp names were unique
+        #[serde(default)]
+        temps: Vec<String>,
+        /// files deleted once every target is replaced
+        #[serde(default)]
+        removed: Vec<String>,
+    }
+
+    #[derive(Default, Clone, Debug, PartialEq, Eq)]
+    /// what Database::recover found and did
+    pub struct Recovery {
+        /// tables whose prepared writes were finished
+        pub replayed: Vec<String>,
+        /// tables whose unprepared writes were thrown away
+        pub discarded: Vec<String>,
+        /// orphaned temp files that were deleted
+        pub removed: Vec<String>,
+    }
+
+    impl Database {
+        /// finishes or discards writes left pending by a crash, cuts a torn line
+        /// off the log, then deletes every orphaned temp file
+        pub fn recover(&self) -> Result<Recovery, TErrors> {
+            self.writable()?;
+            // temp files of writers still running are not orphaned, keep them out
+            let _frozen = self.gate.freeze()?;
+            let mut recovery: Recovery = Recovery::default();
+
+            for entry in self.wal_entries() {
+                for (i, (title, ext)) in entry.targets.into_iter().enumerate() {
+                    let copy: AtomicCopy = AtomicCopy::new(title.clone(), ext.clone(), Vec::new())
+                        .set_temp(entry.temps.get(i).cloned().unwrap_or_default());
+                    let pending: bool = self.backend.exists(&copy.temp_name());
+                    match entry.state {
+                        WalState::Begin => {
+                            if pending {
+                                copy.destroy(self)?;
+                                recovery.discarded.push(title);
+                            }
+                        }
+                        WalState::Prepared => {
+                            // a restore also replaces checksums, indexes and logs
+                            let table: bool = Format::ALL.iter().any(|f| f.ext() == ext);
+                            if pending {
+                                copy.replace(self)?;
+                                let others = Format::ALL.into_iter().filter(|f| f.ext() != ext);
+                                for other in others.filter(|_| table) {
+                                    self.backend.delete(&blob_name(&title, other.ext()))?;
+                                }
+                            }
+                            // the checksum may not have been written before the crash
+                            let Ok(data) = self.backend.read(&blob_name(&title, &ext)) else {
+                                continue;
+                            };
+                            if table {
+                                self.write_sum(&title, &data)?;
+                                if let Ok(record) = Format::decode::<FieldValue>(&data) {
+                                    self.reindex_table(&title, Some(&record))?;
+                                }
+                            }
+                            if pending {
+                                recovery.replayed.push(title);
+                            }
+                        }
+                    }
+                }
+                if entry.state == WalState::Prepared {
+                    for name in entry.removed {
+                        self.backend.delete(&name)?;
+                    }
+                }
+            }
+            self.wal_store(Vec::new())?;
+            self.drop_torn_log_line()?;
+
+            for name in self.orphaned_temps()? {
+                self.backend.delete(&name)?;
+                recovery.removed.push(name);
+            }
+
+            Ok(recovery)
+        }
+
+        /// cuts a line left half written by a crash off the end of the log,
+        /// so the next entry appended starts on a line of its own
+        fn drop_torn_log_line(&self) -> Result<(), TErrors> {
+            let mut buf: Vec<u8> = match self.backend.read(&blob_name(LOG_TABLE, LOG_EXT)) {
+                Ok(buf) => buf,
+                Err(TErrors::FileNotFound) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() || buf.ends_with(b"\n") {
+                return Ok(());
+            }
+            let kept: usize = buf.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            buf.truncate(kept);
+            AtomicCopy::new(LOG_TABLE.to_string(), LOG_EXT.to_string(), buf)
+                .construct(self)?
+                .replace(self)?;
+            Ok(())
+        }
+
+        /// replaces the permanent file of copy through its temp file and updates the
+        /// indexes, logging the intent first so a crash can be recovered from
+        fn write_ahead(&self, copy: &AtomicCopy) -> Result<AtomicCopy, TErrors> {
+            // named before it is logged so recovery knows which temp file to look for
+            let copy: &AtomicCopy = &copy.set_temp(unique_temp(&copy.title));
+            let wal_id: u64 = self.wal_open(WalState::Begin, std::slice::from_ref(copy), &[])?;
+
+            let written: Result<AtomicCopy, TErrors> = copy
+                .construct(self)
+                .and_then(|c| {
+                    self.wal_mark(wal_id, WalState::Prepared)?;
+                    c.replace(self)
+                })
+                .and_then(|c| {
+                    self.write_sum(&c.title, &c.data)?;
+                    // before the entry closes, so recover redoes an update cut short
+                    if let Ok(record) = Format::decode::<FieldValue>(&c.data) {
+                        self.reindex_table(&c.title, Some(&record))?;
+                    }
+                    Ok(c)
+                });
+            if written.is_err() {
+                copy.destroy(self)?;
+            }
+
+            self.wal_close(wal_id)?;
+            written
+        }
+
+        /// pending writes in the write-ahead log. an unreadable log holds none
+        fn wal_entries(&self) -> Vec<WalEntry> {
+            self.backend
+                .read(&blob_name(WAL_TABLE, "json"))
+                .ok()
+                .and_then(|buf| serde_json::from_slice(&buf).ok())
+                .unwrap_or_default()
+        }
+
+        /// overwrites the write-ahead log
+        fn wal_store(&self, entries: Vec<WalEntry>) -> Result<(), TErrors> {
+            if entries.is_empty() {
+                return self.backend.delete(&blob_name(WAL_TABLE, "json"));
+            }
+            let Ok(data) = serde_json::to_vec(&entries) else {
+                return Err(TErrors::StringConvert);
+            };
+            AtomicCopy::new(WAL_TABLE.to_string(), "json".to_string(), data)
+                .construct(self)?
+                .replace(self)?;
+            Ok(())
+        }
+
+        /// logs a write of copies that deletes removed, returning the id of its entry
+        fn wal_open(
+            &self,
+            state: WalState,
+            copies: &[AtomicCopy],
+            removed: &[String],
+        ) -> Result<u64, TErrors> {
+            let _wal: TableGuard = self.lock_file(&blob_name(WAL_TABLE, "json"))?;
+            let mut entries: Vec<WalEntry> = self.wal_entries();
+            let id: u64 = entries.iter().map(|e| e.id).max().unwrap_or_default() + 1;
+            entries.push(WalEntry {
+                id,
+                state,
+                targets: copies
+                    .iter()
+                    .map(|c| (c.title.clone(), c.ext.clone()))
+                    .collect(),
+                temps: copies.iter().map(|c| c.temp_name()).collect(),
+                removed: removed.to_vec(),
+            });
+            self.wal_store(entries)?;
+            Ok(id)
+        }
+
+        /// moves the entry id to state
+        fn wal_mark(&self, id: u64, state: WalState) -> Result<(), TErrors> {
+            let _wal: TableGuard = self.lock_file(&blob_name(WAL_TABLE, "json"))?;
+            let mut entries: Vec<WalEntry> = self.wal_entries();
+            entries
+                .iter_mut()
+                .filter(|e| e.id == id)
+                .for_each(|e| e.state = state);
+            self.wal_store(entries)
+        }
+
+        /// removes the entry id once its write is finished
+        fn wal_close(&self, id: u64) -> Result<(), TErrors> {
+            let _wal: TableGuard = self.lock_file(&blob_name(WAL_TABLE, "json"))?;
+            let mut entries: Vec<WalEntry> = self.wal_entries();
+            entries.retain(|e| e.id != id);
+            self.wal_store(entries)
+        }
+    }
+
+    /// 64 bit FNV-1a checksum of data. stable across platforms and releases
+    pub fn checksum(data: &[u8]) -> u64 {
+        data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+    /// Every file of a snapshot with its checksum
+    pub struct Manifest {
+        /// when the snapshot was taken
+        pub time_stamp: String,
+        /// name and checksum of every file
+        pub files: BTreeMap<String, u64>,
+    }
+
+    impl Database {
+        /// copies every table, log and index into dest while writers are held back.
+        /// fails with NotEmpty unless dest is empty, which also keeps a database
+        /// from being snapshot onto itself. the manifest is written last
+        pub fn snapshot(&self, dest: &Database) -> Result<Manifest, TErrors> {
+            dest.writable()?;
+            if dest.backend.list()?.iter().any(|name| !is_lock(name)) {
+                return Err(TErrors::NotEmpty);
+            }
+            let _frozen = self.gate.freeze()?;
+
+            let mut manifest: Manifest = Manifest {
+                time_stamp: self.time_stamp(),
+                files: BTreeMap::new(),
+            };
+            // pending writes and an earlier manifest are not part of a snapshot
+            let skipped: [String; 2] = [
+                blob_name(WAL_TABLE, "json"),
+                blob_name(MANIFEST_TABLE, "json"),
+            ];
+            for name in self.backend.list()? {
+                if name.ends_with(".temp") || is_lock(&name) || skipped.contains(&name) {
+                    continue;
+                }
+                let data: Vec<u8> = self.backend.read(&name)?;
+                dest.backend.write(&name, &data)?;
+                manifest.files.insert(name, checksum(&data));
+            }
+
+            let Ok(data) = serde_json::to_vec(&manifest) else {
+                return Err(TErrors::StringConvert);
+            };
+            dest.backend
+                .write(&blob_name(MANIFEST_TABLE, "json"), &data)?;
+            dest.forget_indexes()?;
+            Ok(manifest)
+        }
+
+        /// checks every file of snapshot against its manifest.
+        /// fails with CheckError on a missing or altered file
+        pub fn verify_snapshot(snapshot: &Database) -> Result<Manifest, TErrors> {
+            let Ok(manifest) = serde_json::from_slice::<Manifest>(
+                &snapshot.backend.read(&blob_name(MANIFEST_TABLE, "json"))?,
+            ) else {
+                return Err(TErrors::CheckError);
+            };
+            for (name, sum) in &manifest.files {
+                let Ok(data) = snapshot.backend.read(name) else {
+                    return Err(TErrors::CheckError);
+                };
+                if checksum(&data) != *sum {
+                    return Err(TErrors::CheckError);
+                }
+            }
+            Ok(manifest)
+        }
+
+        /// replaces every file of the database with the files of a verified snapshot.
+        /// nothing is touched if the snapshot fails to verify. every file is staged
+        /// and logged to the write-ahead log first, so recover finishes a restore cut short.
+        /// temp files staged by open Transactions are kept
+        pub fn restore(&self, snapshot: &Database) -> Result<Manifest, TErrors> {
+            self.writable()?;
+            let manifest: Manifest = Self::verify_snapshot(snapshot)?;
+
+            // read once and check again so a snapshot changing under us is caught
+            let mut copies: Vec<AtomicCopy> = Vec::new();
+            for (name, sum) in &manifest.files {
+                let data: Vec<u8> = snapshot.backend.read(name)?;
+                if checksum(&data) != *sum {
+                    return Err(TErrors::CheckError);
+                }
+                // snapshots taken before the write-ahead log was skipped may hold it
+                if *name == blob_name(WAL_TABLE, "json") {
+                    continue;
+                }
+                let Some((title, ext)) = name.rsplit_once('.') else {
+                    return Err(TErrors::CheckError);
+                };
+                copies.push(AtomicCopy::new(title.to_string(), ext.to_string(), data));
+            }
+
+            let _frozen = self.gate.freeze()?;
+            let staged: HashSet<String> =
+                self.staging.lock().map_err(|_| TErrors::FileError)?.clone();
+            let removed: Vec<String> = self
+                .backend
+                .list()?
+                .into_iter()
+                .filter(|name| !manifest.files.contains_key(name) && !is_lock(name))
+                .filter(|name| !staged.contains(name) && *name != blob_name(WAL_TABLE, "json"))
+                .collect();
+
+            let mut written: Vec<AtomicCopy> = Vec::new();
+            for copy in &copies {
+                match copy.construct(self) {
+                    Ok(copy) => written.push(copy),
+                    Err(e) => {
+                        for copy in &written {
+                            copy.destroy(self)?;
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            // from here on recover finishes the restore if it is cut short
+            let wal_id: u64 = self.wal_open(WalState::Prepared, &written, &removed)?;
+            for copy in &written {
+                copy.replace(self)?;
+            }
+            for name in &removed {
+                self.backend.delete(name)?;
+            }
+            self.wal_close(wal_id)?;
+
+            // the snapshot may hold other indexes
+            self.forget_indexes()?;
+            Ok(manifest)
+        }
+    }
+
+    #[derive(Default, Clone, Debug, PartialEq, Eq)]
+    /// what Database::verify found and Database::repair fixed
+    pub struct IntegrityReport {
+        /// tables whose data doesnt match their checksum
+        pub corrupt: Vec<String>,
+        /// temp files left behind by a crash
+        pub orphaned: Vec<String>,
+        /// tables whose contents dont match the last change logged for them
+        pub mismatched: Vec<String>,
+        /// tables that cannot be decoded
+        pub unparseable: Vec<String>,
+        /// fields whose secondary index doesnt match the tables
+        pub stale_indexes: Vec<String>,
+        /// tables rewritten from the log and temp files deleted by repair
+        pub repaired: Vec<String>,
+    }
+
+    impl IntegrityReport {
+        /// true when nothing is wrong
+        pub fn is_clean(&self) -> bool {
+            self.corrupt.is_empty()
+                && self.orphaned.is_empty()
+                && self.mismatched.is_empty()
+                && self.unparseable.is_empty()
+                && self.stale_indexes.is_empty()
+        }
+    }
+
+    impl Database {
+        /// checks every table against its checksum and the operation log,
+        /// and looks for orphaned temp files. writers are held back meanwhile
+        pub fn verify(&self) -> Result<IntegrityReport, TErrors> {
+            let _frozen = self.gate.freeze()?;
+            self.check_integrity()
+        }
+
+        /// verifies the database, then rewrites every broken table to the state
+        /// its last logged change left it in and deletes orphaned temp files.
+        /// tables the log knows nothing about are reported but left alone
+        pub fn repair(&self) -> Result<IntegrityReport, TErrors> {
+            self.writable()?;
+            let _frozen = self.gate.freeze()?;
+            let mut report: IntegrityReport = self.check_integrity()?;
+            let last_changes: HashMap<String, FieldValue> = self.last_changes()?;
+
+            let broken: BTreeSet<String> = report
+                .corrupt
+                .iter()
+                .chain(&report.unparseable)
+                .chain(&report.mismatched)
+                .cloned()
+                .collect();
+            for table in broken {
+                let Some(entry) = last_changes.get(&table) else {
+                    continue;
+                };
+                self.write_state(&table, state_after(entry)?, LogOp::Repair)?;
+                report.repaired.push(table);
+            }
+
+            for name in &report.orphaned {
+                self.backend.delete(name)?;
+                report.repaired.push(name.clone());
+            }
+
+            // rebuilt after the tables, from what they hold now
+            for field in &report.stale_indexes {
+                let kind: IndexKind = self.index_kind(field);
+                self.write_index(&self.build_index(field.clone(), kind)?)?;
+                report.repaired.push(blob_name(field, INDEX_EXT));
+            }
+            Ok(report)
+        }
+
+        /// the last successful log entry of every table
+        fn last_changes(&self) -> Result<HashMap<String, FieldValue>, TErrors> {
+            let mut last: HashMap<String, FieldValue> = HashMap::new();
+            for entry in self.log_entries()? {
+                if entry.get("outcome").and_then(|o| o.as_str()) != Some("Success") {
+                    continue;
+                }
+                if let Some(table) = entry.get("table").and_then(|t| t.as_str()) {
+                    last.insert(table.to_string(), entry);
+                }
+            }
+            Ok(last)
+        }
+
+        /// verify for callers already holding the gate
+        fn check_integrity(&self) -> Result<IntegrityReport, TErrors> {
+            let mut report: IntegrityReport = IntegrityReport::default();
+            let last_changes: HashMap<String, FieldValue> = self.last_changes()?;
+
+            let mut tables: BTreeSet<String> = self.stored_tables()?.into_iter().collect();
+            tables.extend(last_changes.keys().cloned());
+
+            for table in tables {
+                let stored: Option<Vec<u8>> = self.stored_blob(&table);
+
+                let state: Option<FieldValue> = match &stored {
+                    None => None,
+                    Some(data) if !self.sum_matches(&table, data) => {
+                        report.corrupt.push(table);
+                        continue;
+                    }
+                    Some(data) => match Format::decode::<FieldValue>(data) {
+                        Ok(state) => Some(state),
+                        Err(_) => {
+                            report.unparseable.push(table);
+                            continue;
+                        }
+                    },
+                };
+
+                let Some(entry) = last_changes.get(&table) else {
+                    continue;
+                };
+                let logged: Option<u64> = entry.get("later_id").and_then(|l| l.as_u64());
+                if logged != Some(state.as_ref().map(state_hash).unwrap_or_default()) {
+                    report.mismatched.push(table);
+                }
+            }
+
+            for field in self.indexed_fields()? {
+                let stored: Option<SecondaryIndex> = self.index(&field).ok().flatten();
+                let built: SecondaryIndex =
+                    self.build_index(field.clone(), self.index_kind(&field))?;
+                if stored != Some(built) {
+                    report.stale_indexes.push(field);
+                }
+            }
+
+            report.orphaned = self.orphaned_temps()?;
+            Ok(report)
+        }
+
+        /// kind of the index on field. an unreadable index is rebuilt as Ordered,
+        /// which answers every lookup
+        fn index_kind(&self, field: &str) -> IndexKind {
+            match self.index(field) {
+                Ok(Some(index)) => index.kind,
+                _ => IndexKind::Ordered,
+            }
+        }
+    }
+
+    #[derive(Default, Clone, Debug, Hash, Serialize, Deserialize)]
+    /// creates a collection of type T
+    #[serde(bound = "")]
+    pub struct Collection<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
+        pub inner: Vec<T>,
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Collect<T>
+        for Collection<T>
+    {
+        fn new(inner: Vec<T>) -> Self {
+            Self { inner }
+        }
+
+        fn collect(&self, frag: Fragment<T>) -> Result<Self, TErrors>
+        where
+            Self: Sized,
+        {
+            let all_inferred: Vec<T> = frag
+                .get_all_infer()?
+                .into_iter()
+                .map(|f| f.inner)
+                .collect();
+
+            Ok(Self {
+                inner: all_inferred,
+            })
+        }
+
+        fn append(&mut self, obj: T) -> Self {
+            self.inner.push(obj);
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+
+        fn remove(&self, obj: T) -> Self
+        where
+            T: PartialEq,
+        {
+            Self {
+                inner: self
+                    .inner
+                    .clone()
+                    .into_iter()
+                    .filter(|f| *f != obj)
+                    .collect::<Vec<T>>(),
+            }
+        }
+
+        fn update_index(&self, index: usize, new_obj: T) -> Self {
+            Self {
+                inner: self
+                    .inner
+                    .clone()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, mut f)| {
+                        if i == index {
+                            f = new_obj.clone()
+                        }
+                        f
+                    })
+                    .collect::<Vec<T>>(),
+            }
+        }
+
+        fn get_by_key<K: PartialEq>(&self, key: &K) -> Option<T>
+        where
+            T: Keyed<K>,
+        {
+            self.inner.iter().find(|f| f.key() == *key).cloned()
+        }
+
+        fn remove_by_key<K: PartialEq>(&self, key: &K) -> Self
+        where
+            T: Keyed<K>,
+        {
+            Self {
+                inner: self
+                    .inner
+                    .iter()
+                    .filter(|f| f.key() != *key)
+                    .cloned()
+                    .collect::<Vec<T>>(),
+            }
+        }
+
+        fn update_by_key<K: PartialEq>(&self, new_obj: T) -> Self
+        where
+            T: Keyed<K>,
+        {
+            let key: K = new_obj.key();
+            Self {
+                inner: self
+                    .inner
+                    .iter()
+                    .map(|f| if f.key() == key { new_obj.clone() } else { f.clone() })
+                    .collect::<Vec<T>>(),
+            }
+        }
+
+        fn dedupe_by_key<K: Eq + Hash>(&self) -> Self
+        where
+            T: Keyed<K>,
+        {
+            let mut seen: HashSet<K> = HashSet::new();
+            Self {
+                inner: self
+                    .inner
+                    .iter()
+                    .filter(|f| seen.insert(f.key()))
+                    .cloned()
+                    .collect::<Vec<T>>(),
+            }
+        }
+
+        fn write_to_file(&self, db: &Database, title: String) -> Result<(), TErrors>
+        where
+            Self: Serialize + DeserializeOwned + Clone + Debug,
+        {
+            let _writing = db.lock_tables(&[&title])?;
+            let format: Format = db.table_format(&title);
+
+            let prior: Option<Vec<u8>> = db.stored_blob(&title);
+            let later: Result<AtomicCopy, TErrors> = db.write_ahead(&AtomicCopy::new(
+                title.clone(),
+                format.ext().to_string(),
+                format.encode(&self.inner)?,
+            ));
+            log_state(db, LogOp::Write, &title, prior, later.clone())?;
+            later?;
+            Ok(())
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> ToHash for Collection<T> {
+        fn to_hash(&self) -> Result<HashMap<String, FieldValue>, TErrors> {
+            value_to_hash(self)
+        }
+
+        fn to_hash_opt(&self) -> Result<HashMap<String, Option<FieldValue>>, TErrors> {
+            Ok(self.to_hash()?.convert_opt())
+        }
+
+        fn to_hash_vec(&self) -> Result<HashMap<String, Vec<FieldValue>>, TErrors> {
+            let Ok(value) = serde_json::to_value(self.clone()) else {
+                return Err(TErrors::StringConvert);
+            };
+            serde_json::from_value::<HashMap<String, Vec<FieldValue>>>(value).map_err(|_| {
+                return TErrors::HashConvert; 
+            })
+        }
+
+        fn zip(&self) -> Result<Vec<(String, FieldValue)>, TErrors> {
+            Ok(self
+                .to_hash_vec()?
+                .into_iter()
+                .flat_map(|(key, values)| values.into_iter().map(move |f| (key.clone(), f)))
+                .collect())
+        }
+    }
+
+    /// extension of the blobs secondary indexes are stored in
+    const INDEX_EXT: &str = "idx";
+
+    /// returns the field at path. nested fields are separated by '.'
+    fn field_at<'a>(record: &'a FieldValue, path: &str) -> Option<&'a FieldValue> {
+        path.split('.').try_fold(record, |value, part| value.get(part))
+    }
+
+    /// total order over field values:
+    /// null < bool < number < string < array < object
+    fn compare_values(a: &FieldValue, b: &FieldValue) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        fn rank(value: &FieldValue) -> u8 {
+            match value {
+                FieldValue::Null => 0,
+                FieldValue::Bool(_) => 1,
+                FieldValue::Number(_) => 2,
+                FieldValue::String(_) => 3,
+                FieldValue::Array(_) => 4,
+                FieldValue::Object(_) => 5,
+            }
+        }
+
+        match (a, b) {
+            (FieldValue::Bool(x), FieldValue::Bool(y)) => x.cmp(y),
+            (FieldValue::Number(x), FieldValue::Number(y)) => {
+                if let (Some(x), Some(y)) = (x.as_i64(), y.as_i64()) {
+                    x.cmp(&y)
+                } else if let (Some(x), Some(y)) = (x.as_u64(), y.as_u64()) {
+                    x.cmp(&y)
+                } else {
+                    let x: f64 = x.as_f64().unwrap_or_default();
+                    let y: f64 = y.as_f64().unwrap_or_default();
+                    x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+                }
+            }
+            (FieldValue::String(x), FieldValue::String(y)) => x.cmp(y),
+            (FieldValue::Array(x), FieldValue::Array(y)) => x
+                .iter()
+                .zip(y.iter())
+                .map(|(x, y)| compare_values(x, y))
+                .find(|o| o.is_ne())
+                .unwrap_or(x.len().cmp(&y.len())),
+            (FieldValue::Object(_), FieldValue::Object(_)) => a.to_string().cmp(&b.to_string()),
+            _ => rank(a).cmp(&rank(b)),
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    /// a FieldValue usable as a key of a secondary index.
+    /// strings are trimmed, matching build_where.
+    struct IndexValue(FieldValue);
+
+    impl IndexValue {
+        fn new(value: &FieldValue) -> Self {
+            match value {
+                FieldValue::String(s) => Self(FieldValue::String(s.trim().to_string())),
+                other => Self(other.clone()),
+            }
+        }
+    }
+
+    impl PartialEq for IndexValue {
+        fn eq(&self, other: &Self) -> bool {
+            compare_values(&self.0, &other.0).is_eq()
+        }
+    }
+
+    impl Eq for IndexValue {}
+
+    impl PartialOrd for IndexValue {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for IndexValue {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            compare_values(&self.0, &other.0)
+        }
+    }
+
+    impl Hash for IndexValue {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            match &self.0 {
+                // numbers that compare equal must hash equal
+                FieldValue::Number(n) => {
+                    let f: f64 = n.as_f64().unwrap_or_default();
+                    (if f == 0.0 { 0.0 } else { f }).to_bits().hash(state)
+                }
+                other => other.to_string().hash(state),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    /// the kinds of secondary index
+    pub enum IndexKind {
+        /// answers equality lookups
+        Hash,
+        /// answers equality and range lookups
+        Ordered,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    /// entries of a secondary index
+    enum IndexEntries {
+        Hash(HashMap<IndexValue, BTreeSet<String>>),
+        Ordered(BTreeMap<IndexValue, BTreeSet<String>>),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    /// the persisted shape of a SecondaryIndex
+    struct StoredIndex {
+        field: String,
+        kind: IndexKind,
+        entries: Vec<(FieldValue, BTreeSet<String>)>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    /// Maps the values of one field to the tables holding them.
+    /// persisted next to the tables and kept up to date on write.
+    pub struct SecondaryIndex {
+        pub field: String,
+        pub kind: IndexKind,
+        entries: IndexEntries,
+    }
+
+    impl SecondaryIndex {
+        /// creates new empty instance
+        pub fn new(field: String, kind: IndexKind) -> Self {
+            let entries: IndexEntries = match kind {
+                IndexKind::Hash => IndexEntries::Hash(HashMap::new()),
+                IndexKind::Ordered => IndexEntries::Ordered(BTreeMap::new()),
+            };
+            Self {
+                field,
+                kind,
+                entries,
+            }
+        }
+
+        /// adds table under the value its record holds for the indexed field.
+        /// a table holding an array, like a Collection, is added under every record in it
+        pub fn insert(&mut self, table: &str, record: &FieldValue) {
+            if let FieldValue::Array(records) = record {
+                records.iter().for_each(|record| self.insert(table, record));
+                return;
+            }
+            let Some(value) = field_at(record, &self.field) else {
+                return;
+            };
+            let key: IndexValue = IndexValue::new(value);
+            let tables: &mut BTreeSet<String> = match &mut self.entries {
+                IndexEntries::Hash(entries) => entries.entry(key).or_default(),
+                IndexEntries::Ordered(entries) => entries.entry(key).or_default(),
+            };
+            tables.insert(table.to_string());
+        }
+
+        /// removes table from every value
+        pub fn remove(&mut self, table: &str) {
+            match &mut self.entries {
+                IndexEntries::Hash(entries) => entries.retain(|_, tables| {
+                    tables.remove(table);
+                    !tables.is_empty()
+                }),
+                IndexEntries::Ordered(entries) => entries.retain(|_, tables| {
+                    tables.remove(table);
+                    !tables.is_empty()
+                }),
+            }
+        }
+
+        /// returns the tables whose record holds value
+        pub fn get(&self, value: &FieldValue) -> Vec<String> {
+            let key: IndexValue = IndexValue::new(value);
+            let tables: Option<&BTreeSet<String>> = match &self.entries {
+                IndexEntries::Hash(entries) => entries.get(&key),
+                IndexEntries::Ordered(entries) => entries.get(&key),
+            };
+            tables.map(|t| t.iter().cloned().collect()).unwrap_or_default()
+        }
+
+        /// returns the tables whose record holds a value between lower and upper.
+        /// bounds that leave no value between them give none.
+        /// hash indexes have to visit every entry to answer this
+        pub fn range(&self, lower: Bound<&FieldValue>, upper: Bound<&FieldValue>) -> Vec<String> {
+            let lower: Bound<IndexValue> = lower.map(IndexValue::new);
+            let upper: Bound<IndexValue> = upper.map(IndexValue::new);
+            // BTreeMap::range panics on these
+            let empty: bool = match (&lower, &upper) {
+                (Bound::Included(l), Bound::Included(u)) => l > u,
+                (
+                    Bound::Included(l) | Bound::Excluded(l),
+                    Bound::Included(u) | Bound::Excluded(u),
+                ) => l >= u,
+                _ => false,
+            };
+            if empty {
+                return Vec::new();
+            }
+            let mut tables: BTreeSet<String> = BTreeSet::new();
+            match &self.entries {
+                IndexEntries::Hash(entries) => entries
+                    .iter()
+                    .filter(|(k, _)| (lower.as_ref(), upper.as_ref()).contains(*k))
+                    .for_each(|(_, t)| tables.extend(t.iter().cloned())),
+                IndexEntries::Ordered(entries) => entries
+                    .range((lower, upper))
+                    .for_each(|(_, t)| tables.extend(t.iter().cloned())),
+            }
+            tables.into_iter().collect()
+        }
+
+        fn to_bytes(&self) -> Result<Vec<u8>, TErrors> {
+            let entries: Vec<(FieldValue, BTreeSet<String>)> = match &self.entries {
+                IndexEntries::Hash(entries) => {
+                    entries.iter().map(|(k, v)| (k.0.clone(), v.clone())).collect()
+                }
+                IndexEntries::Ordered(entries) => {
+                    entries.iter().map(|(k, v)| (k.0.clone(), v.clone())).collect()
+                }
+            };
+            serde_json::to_vec(&StoredIndex {
+                field: self.field.clone(),
+                kind: self.kind,
+                entries,
+            })
+            .map_err(|_| {
+                return TErrors::IndexError;
+            })
+        }
+
+        fn from_bytes(data: &[u8]) -> Result<Self, TErrors> {
+            let Ok(stored) = serde_json::from_slice::<StoredIndex>(data) else {
+                return Err(TErrors::IndexError);
+            };
+            let mut index: SecondaryIndex = Self::new(stored.field, stored.kind);
+            for (value, tables) in stored.entries {
+                let key: IndexValue = IndexValue::new(&value);
+                match &mut index.entries {
+                    IndexEntries::Hash(entries) => entries.insert(key, tables),
+                    IndexEntries::Ordered(entries) => entries.insert(key, tables),
+                };
+            }
+            Ok(index)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    /// A condition a record has to meet. fields may be nested like 'owner.name'
+    pub enum Filter {
+        /// field equals value. strings are compared after trimming
+        Eq(String, FieldValue),
+        /// field does not equal value
+        Ne(String, FieldValue),
+        /// field is less than value
+        Lt(String, FieldValue),
+        /// field is less than or equal to value
+        Le(String, FieldValue),
+        /// field is greater than value
+        Gt(String, FieldValue),
+        /// field is greater than or equal to value
+        Ge(String, FieldValue),
+        /// string field contains the substring, or array field contains the element
+        Contains(String, FieldValue),
+        /// string field starts with the prefix
+        StartsWith(String, String),
+        /// field equals one of the values
+        InList(String, Vec<FieldValue>),
+        /// field is null or missing
+        IsNull(String),
+        /// every filter matches. empty matches everything
+        And(Vec<Filter>),
+        /// at least one filter matches
+        Or(Vec<Filter>),
+        /// the filter does not match
+        Not(Box<Filter>),
+    }
+
+    impl Filter {
+        /// returns true if record meets the condition
+        pub fn matches(&self, record: &FieldValue) -> bool {
+            // ordering comparisons only apply between values of the same kind
+            let compare = |field: &String, value: &FieldValue| {
+                field_at(record, field).and_then(|stored| {
+                    if std::mem::discriminant(stored) == std::mem::discriminant(value) {
+                        Some(compare_values(stored, value))
+                    } else {
+                        None
+                    }
+                })
+            };
+
+            match self {
+                Filter::Eq(field, value) => {
+                    field_at(record, field).is_some_and(|stored| values_match(stored, value))
+                }
+                Filter::Ne(field, value) => !Filter::Eq(field.clone(), value.clone()).matches(record),
+                Filter::Lt(field, value) => compare(field, value).is_some_and(|o| o.is_lt()),
+                Filter::Le(field, value) => compare(field, value).is_some_and(|o| o.is_le()),
+                Filter::Gt(field, value) => compare(field, value).is_some_and(|o| o.is_gt()),
+                Filter::Ge(field, value) => compare(field, value).is_some_and(|o| o.is_ge()),
+                Filter::Contains(field, value) => match (field_at(record, field), value) {
+                    (Some(FieldValue::String(stored)), FieldValue::String(part)) => {
+                        stored.contains(part.as_str())
+                    }
+                    (Some(FieldValue::Array(items)), value) => {
+                        items.iter().any(|item| values_match(item, value))
+                    }
+                    _ => false,
+                },
+                Filter::StartsWith(field, prefix) => field_at(record, field)
+                    .and_then(|stored| stored.as_str())
+                    .is_some_and(|stored| stored.starts_with(prefix.as_str())),
+                Filter::InList(field, values) => field_at(record, field)
+                    .is_some_and(|stored| values.iter().any(|value| values_match(stored, value))),
+                Filter::IsNull(field) => field_at(record, field).is_none_or(|stored| stored.is_null()),
+                Filter::And(filters) => filters.iter().all(|f| f.matches(record)),
+                Filter::Or(filters) => filters.iter().any(|f| f.matches(record)),
+                Filter::Not(filter) => !filter.matches(record),
+            }
+        }
+
+        /// narrows the tables worth reading using secondary indexes.
+        /// None means every table has to be read
+        fn candidates(&self, db: &Database) -> Result<Option<BTreeSet<String>>, TErrors> {
+            let range = |field: &String, lower: Bound<&FieldValue>, upper: Bound<&FieldValue>| {
+                Ok(db
+                    .index(field)?
+                    .map(|index| index.range(lower, upper).into_iter().collect()))
+            };
+
+            match self {
+                Filter::Eq(field, value) => {
+                    Ok(db.index(field)?.map(|index| index.get(value).into_iter().collect()))
+                }
+                Filter::Lt(field, value) => range(field, Bound::Unbounded, Bound::Excluded(value)),
+                Filter::Le(field, value) => range(field, Bound::Unbounded, Bound::Included(value)),
+                Filter::Gt(field, value) => range(field, Bound::Excluded(value), Bound::Unbounded),
+                Filter::Ge(field, value) => range(field, Bound::Included(value), Bound::Unbounded),
+                Filter::InList(field, values) => Ok(db.index(field)?.map(|index| {
+                    values.iter().flat_map(|value| index.get(value)).collect()
+                })),
+                Filter::And(filters) => {
+                    let mut narrowed: Option<BTreeSet<String>> = None;
+                    for filter in filters {
+                        if let Some(tables) = filter.candidates(db)? {
+                            narrowed = Some(match narrowed {
+                                Some(current) => current.intersection(&tables).cloned().collect(),
+                                None => tables,
+                            });
+                        }
+                    }
+                    Ok(narrowed)
+                }
+                Filter::Or(filters) => {
+                    let mut widened: BTreeSet<String> = BTreeSet::new();
+                    for filter in filters {
+                        let Some(tables) = filter.candidates(db)? else {
+                            return Ok(None);
+                        };
+                        widened.extend(tables);
+                    }
+                    Ok(Some(widened))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    /// Composable query over tables of type T.
+    /// every condition added is combined with the previous ones using and
+    pub struct Query<T> {
+        filter: Filter,
+        order_by: Vec<(String, bool)>,
+        limit: Option<usize>,
+        offset: usize,
+        _records: PhantomData<T>,
+    }
+
+    impl<T> Default for Query<T> {
+        fn default() -> Self {
+            Self {
+                filter: Filter::And(Vec::new()),
+                order_by: Vec::new(),
+                limit: None,
+                offset: 0,
+                _records: PhantomData,
+            }
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Query<T> {
+        /// creates new instance matching every record
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// the condition built so far
+        pub fn filter(&self) -> &Filter {
+            &self.filter
+        }
+
+        /// adds a condition
+        pub fn when(&self, filter: Filter) -> Self {
+            let filter: Filter = match self.filter.clone() {
+                Filter::And(mut filters) => {
+                    filters.push(filter);
+                    Filter::And(filters)
+                }
+                current => Filter::And(vec![current, filter]),
+            };
+            Self {
+                filter,
+                ..self.clone()
+            }
+        }
+
+        /// field equals value
+        pub fn eq<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Eq(field.to_string(), value.into()))
+        }
+
+        /// field does not equal value
+        pub fn ne<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Ne(field.to_string(), value.into()))
+        }
+
+        /// field is less than value
+        pub fn lt<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Lt(field.to_string(), value.into()))
+        }
+
+        /// field is less than or equal to value
+        pub fn le<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Le(field.to_string(), value.into()))
+        }
+
+        /// field is greater than value
+        pub fn gt<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Gt(field.to_string(), value.into()))
+        }
+
+        /// field is greater than or equal to value
+        pub fn ge<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Ge(field.to_string(), value.into()))
+        }
+
+        /// string field contains the substring, or array field contains the element
+        pub fn contains<V: Into<FieldValue>>(&self, field: &str, value: V) -> Self {
+            self.when(Filter::Contains(field.to_string(), value.into()))
+        }
+
+        /// string field starts with the prefix
+        pub fn starts_with(&self, field: &str, prefix: &str) -> Self {
+            self.when(Filter::StartsWith(field.to_string(), prefix.to_string()))
+        }
+
+        /// field equals one of the values
+        pub fn in_list<V: Into<FieldValue>>(&self, field: &str, values: Vec<V>) -> Self {
+            self.when(Filter::InList(
+                field.to_string(),
+                values.into_iter().map(|v| v.into()).collect(),
+            ))
+        }
+
+        /// field is null or missing
+        pub fn is_null(&self, field: &str) -> Self {
+            self.when(Filter::IsNull(field.to_string()))
+        }
+
+        /// both this and the other query have to match
+        pub fn and(&self, other: &Query<T>) -> Self {
+            self.when(other.filter.clone())
+        }
+
+        /// this or the other query has to match
+        pub fn or(&self, other: &Query<T>) -> Self {
+            Self {
+                filter: Filter::Or(vec![self.filter.clone(), other.filter.clone()]),
+                ..self.clone()
+            }
+        }
+
+        /// negates the conditions built so far
+        pub fn not(&self) -> Self {
+            Self {
+                filter: Filter::Not(Box::new(self.filter.clone())),
+                ..self.clone()
+            }
+        }
+
+        /// sorts ascending by field. later calls break ties of earlier ones
+        pub fn order_by(&self, field: &str) -> Self {
+            let mut order_by: Vec<(String, bool)> = self.order_by.clone();
+            order_by.push((field.to_string(), true));
+            Self {
+                order_by,
+                ..self.clone()
+            }
+        }
+
+        /// sorts descending by field. later calls break ties of earlier ones
+        pub fn order_by_desc(&self, field: &str) -> Self {
+            let mut order_by: Vec<(String, bool)> = self.order_by.clone();
+            order_by.push((field.to_string(), false));
+            Self {
+                order_by,
+                ..self.clone()
+            }
+        }
+
+        /// returns at most limit records
+        pub fn limit(&self, limit: usize) -> Self {
+            Self {
+                limit: Some(limit),
+                ..self.clone()
+            }
+        }
+
+        /// skips the first offset records
+        pub fn offset(&self, offset: usize) -> Self {
+            Self {
+                offset,
+                ..self.clone()
+            }
+        }
+
+        /// filters, sorts and pages rows of (record as FieldValue, item)
+        fn apply<R>(&self, rows: Vec<(FieldValue, R)>) -> Vec<R> {
+            let mut rows: Vec<(FieldValue, R)> = rows
+                .into_iter()
+                .filter(|(record, _)| self.filter.matches(record))
+                .collect();
+
+            rows.sort_by(|(a, _), (b, _)| {
+                self.order_by
+                    .iter()
+                    .map(|(field, ascending)| {
+                        let ordering = compare_values(
+                            field_at(a, field).unwrap_or(&FieldValue::Null),
+                            field_at(b, field).unwrap_or(&FieldValue::Null),
+                        );
+                        if *ascending { ordering } else { ordering.reverse() }
+                    })
+                    .find(|o| o.is_ne())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            rows.into_iter()
+                .skip(self.offset)
+                .take(self.limit.unwrap_or(usize::MAX))
+                .map(|(_, item)| item)
+                .collect()
+        }
+
+        /// runs the query against every Fragment<T> table of the database,
+        /// using secondary indexes where they help
+        pub fn run(&self, db: &Database) -> Result<Vec<Fragment<T>>, TErrors> {
+            let tables: Vec<String> = match self.filter.candidates(db)? {
+                Some(tables) => tables.into_iter().filter(|t| !is_page(t)).collect(),
+                None => db.tables()?,
+            };
+
+            let mut rows: Vec<(FieldValue, Fragment<T>)> = Vec::new();
+            for table in tables {
+                let Ok(frag) = db.fragment(T::default()).read_table(table) else {
+                    continue;
+                };
+                let Ok(record) = serde_json::to_value(&frag.inner) else {
+                    return Err(TErrors::HashConvert);
+                };
+                rows.push((record, frag));
+            }
+
+            Ok(self.apply(rows))
+        }
+    }
+
+    /// returns the numbers stored in field, skipping records where it isnt numeric
+    fn numbers(rows: &[FieldValue], field: &str) -> Vec<f64> {
+        rows.iter()
+            .filter_map(|row| field_at(row, field)?.as_f64())
+            .collect()
+    }
+
+    /// aggregate operations over a set of records.
+    /// only rows has to be implemented
+    pub trait Aggregate {
+        /// every record as a FieldValue
+        fn rows(&self) -> Result<Vec<FieldValue>, TErrors>;
+
+        /// number of records
+        fn count(&self) -> Result<usize, TErrors> {
+            Ok(self.rows()?.len())
+        }
+
+        /// sum of a numeric field. non numeric values are skipped
+        fn sum(&self, field: &str) -> Result<f64, TErrors> {
+            Ok(numbers(&self.rows()?, field).into_iter().sum())
+        }
+
+        /// smallest value of a numeric field
+        fn min(&self, field: &str) -> Result<Option<f64>, TErrors> {
+            Ok(numbers(&self.rows()?, field).into_iter().reduce(f64::min))
+        }
+
+        /// largest value of a numeric field
+        fn max(&self, field: &str) -> Result<Option<f64>, TErrors> {
+            Ok(numbers(&self.rows()?, field).into_iter().reduce(f64::max))
+        }
+
+        /// mean of a numeric field
+        fn avg(&self, field: &str) -> Result<Option<f64>, TErrors> {
+            let values: Vec<f64> = numbers(&self.rows()?, field);
+            if values.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(values.iter().sum::<f64>() / values.len() as f64))
+        }
+
+        /// splits the records by the value of field. missing fields group under null.
+        /// every Group can be aggregated on its own
+        fn group_by(&self, field: &str) -> Result<Vec<Group>, TErrors> {
+            let mut groups: Vec<Group> = Vec::new();
+            for row in self.rows()? {
+                let key: FieldValue = field_at(&row, field).cloned().unwrap_or(FieldValue::Null);
+                match groups.iter_mut().find(|g| compare_values(&g.key, &key).is_eq()) {
+                    Some(group) => group.rows.push(row),
+                    None => groups.push(Group {
+                        key,
+                        rows: vec![row],
+                    }),
+                }
+            }
+            groups.sort_by(|a, b| compare_values(&a.key, &b.key));
+            Ok(groups)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    /// records sharing the same value of the field passed to group_by
+    pub struct Group {
+        pub key: FieldValue,
+        pub rows: Vec<FieldValue>,
+    }
+
+    impl Aggregate for Group {
+        fn rows(&self) -> Result<Vec<FieldValue>, TErrors> {
+            Ok(self.rows.clone())
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Aggregate for Collection<T> {
+        fn rows(&self) -> Result<Vec<FieldValue>, TErrors> {
+            self.inner
+                .iter()
+                .map(|f| {
+                    serde_json::to_value(f).map_err(|_| {
+                        return TErrors::HashConvert;
+                    })
+                })
+                .collect()
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Aggregate for Table<T> {
+        fn rows(&self) -> Result<Vec<FieldValue>, TErrors> {
+            self.to_collection()?.rows()
+        }
+    }
+
+    /// merges a left and a right row into one.
+    /// columns in conflicts are prefixed with the name of their side,
+    /// the join key is kept once under its own name.
+    fn merge_rows(
+        (left_name, left): (&str, Option<HashMap<String, FieldValue>>),
+        (right_name, right): (&str, Option<HashMap<String, FieldValue>>),
+        conflicts: &HashSet<String>,
+        join_key: Option<&str>,
+    ) -> HashMap<String, FieldValue> {
+        let mut merged: HashMap<String, FieldValue> = HashMap::new();
+        for (name, row) in [(right_name, right), (left_name, left)] {
+            for (column, value) in row.unwrap_or_default() {
+                if join_key == Some(column.as_str()) {
+                    merged.insert(column, value);
+                } else if conflicts.contains(&column) {
+                    merged.insert(format!("{}.{}", name, column), value);
+                } else {
+                    merged.insert(column, value);
+                }
+            }
+        }
+        merged
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    /// the kinds of join
+    pub enum JoinKind {
+        /// only pairs found on both sides
+        Inner,
+        /// every left row, paired when possible
+        Left,
+        /// every right row, paired when possible
+        Right,
+        /// every row of both sides, paired when possible
+        Full,
+    }
+
+    /// a row of a join, either side may be missing
+    pub type JoinPair<A, B> = (Option<A>, Option<B>);
+
+    #[derive(Debug, Clone)]
+    /// Joins records of type A with records of type B
+    /// where A's left_on field equals B's right_on field.
+    /// null or missing keys never match.
+    pub struct Join<A: Serialize + Clone, B: Serialize + Clone> {
+        left: Vec<A>,
+        right: Vec<B>,
+        left_on: String,
+        right_on: String,
+        left_name: String,
+        right_name: String,
+    }
+
+    impl<A: Serialize + Clone, B: Serialize + Clone> Join<A, B> {
+        /// creates new instance. the sides are named 'left' and 'right'
+        pub fn new(left: Vec<A>, right: Vec<B>, left_on: &str, right_on: &str) -> Self {
+            Self {
+                left,
+                right,
+                left_on: left_on.to_string(),
+                right_on: right_on.to_string(),
+                left_name: "left".to_string(),
+                right_name: "right".to_string(),
+            }
+        }
+
+        /// joins the records of two tables. the sides are named after the tables
+        pub fn tables(
+            left: &Table<A>,
+            right: &Table<B>,
+            left_on: &str,
+            right_on: &str,
+        ) -> Result<Self, TErrors>
+        where
+            A: DeserializeOwned + Debug + Hash + Default,
+            B: DeserializeOwned + Debug + Hash + Default,
+        {
+            Ok(Self::new(
+                left.to_collection()?.inner,
+                right.to_collection()?.inner,
+                left_on,
+                right_on,
+            )
+            .set_names(left.name(), right.name()))
+        }
+
+        /// sets the names used to prefix conflicting columns in merged rows
+        pub fn set_names(&self, left_name: &str, right_name: &str) -> Self {
+            Self {
+                left_name: left_name.to_string(),
+                right_name: right_name.to_string(),
+                ..self.clone()
+            }
+        }
+
+        /// converts both sides into rows
+        fn rows(&self) -> Result<(Vec<FieldValue>, Vec<FieldValue>), TErrors> {
+            let left = self
+                .left
+                .iter()
+                .map(|f| serde_json::to_value(f).map_err(|_| TErrors::HashConvert))
+                .collect::<Result<Vec<FieldValue>, TErrors>>()?;
+            let right = self
+                .right
+                .iter()
+                .map(|f| serde_json::to_value(f).map_err(|_| TErrors::HashConvert))
+                .collect::<Result<Vec<FieldValue>, TErrors>>()?;
+            Ok((left, right))
+        }
+
+        /// indexes of matching rows. unmatched rows pair with None.
+        /// the right rows are hashed by key once, then each left row looks its key up
+        fn pairs(&self, kind: JoinKind) -> Result<Vec<JoinPair<usize, usize>>, TErrors> {
+            let (left, right) = self.rows()?;
+            let key = |row: &FieldValue, field: &str| {
+                field_at(row, field).filter(|v| !v.is_null()).cloned()
+            };
+
+            let mut right_keys: HashMap<IndexValue, Vec<(usize, FieldValue)>> = HashMap::new();
+            for (r, right_row) in right.iter().enumerate() {
+                if let Some(right_key) = key(right_row, &self.right_on) {
+                    right_keys
+                        .entry(IndexValue::new(&right_key))
+                        .or_default()
+                        .push((r, right_key));
+                }
+            }
+
+            let mut pairs: Vec<JoinPair<usize, usize>> = Vec::new();
+            let mut right_matched: Vec<bool> = vec![false; right.len()];
+
+            for (l, left_row) in left.iter().enumerate() {
+                let mut matched: bool = false;
+                if let Some(left_key) = key(left_row, &self.left_on) {
+                    let candidates = right_keys.get(&IndexValue::new(&left_key));
+                    for (r, right_key) in candidates.into_iter().flatten() {
+                        // keys sharing a bucket may still not match, like 1 and 1.0
+                        if values_match(&left_key, right_key) {
+                            pairs.push((Some(l), Some(*r)));
+                            right_matched[*r] = true;
+                            matched = true;
+                        }
+                    }
+                }
+                if !matched && matches!(kind, JoinKind::Left | JoinKind::Full) {
+                    pairs.push((Some(l), None));
+                }
+            }
+
+            if matches!(kind, JoinKind::Right | JoinKind::Full) {
+                right_matched
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, matched)| !**matched)
+                    .for_each(|(r, _)| pairs.push((None, Some(r))));
+            }
+
+            Ok(pairs)
+        }
+
+        /// typed pairs for any kind of join
+        fn typed(&self, kind: JoinKind) -> Result<Vec<JoinPair<A, B>>, TErrors> {
+            Ok(self
+                .pairs(kind)?
+                .into_iter()
+                .map(|(l, r)| {
+                    (
+                        l.map(|l| self.left[l].clone()),
+                        r.map(|r| self.right[r].clone()),
+                    )
+                })
+                .collect())
+        }
+
+        /// only pairs found on both sides
+        pub fn inner(&self) -> Result<Vec<(A, B)>, TErrors> {
+            Ok(self
+                .typed(JoinKind::Inner)?
+                .into_iter()
+                .filter_map(|(a, b)| Some((a?, b?)))
+                .collect())
+        }
+
+        /// every left row, paired when possible
+        pub fn left(&self) -> Result<Vec<(A, Option<B>)>, TErrors> {
+            Ok(self
+                .typed(JoinKind::Left)?
+                .into_iter()
+                .filter_map(|(a, b)| Some((a?, b)))
+                .collect())
+        }
+
+        /// every right row, paired when possible
+        pub fn right(&self) -> Result<Vec<(Option<A>, B)>, TErrors> {
+            Ok(self
+                .typed(JoinKind::Right)?
+                .into_iter()
+                .filter_map(|(a, b)| Some((a, b?)))
+                .collect())
+        }
+
+        /// every row of both sides, paired when possible
+        pub fn full(&self) -> Result<Vec<JoinPair<A, B>>, TErrors> {
+            self.typed(JoinKind::Full)
+        }
+
+        /// merges every pair into a single row.
+        /// columns present on both sides are renamed 'left_name.column' and 'right_name.column'.
+        /// when both sides join on the same field name it is kept once, unprefixed.
+        /// columns of a missing side are left out.
+        pub fn merged(&self, kind: JoinKind) -> Result<Vec<HashMap<String, FieldValue>>, TErrors> {
+            let (left, right) = self.rows()?;
+            let columns = |rows: &[FieldValue]| {
+                rows.iter()
+                    .filter_map(|row| row.as_object())
+                    .flat_map(|row| row.keys().cloned())
+                    .collect::<HashSet<String>>()
+            };
+            let join_key: Option<&str> = if self.left_on == self.right_on {
+                Some(self.left_on.as_str())
+            } else {
+                None
+            };
+            let conflicts: HashSet<String> = columns(&left)
+                .intersection(&columns(&right))
+                .filter(|column| join_key != Some(column.as_str()))
+                .cloned()
+                .collect();
+
+            let as_hash = |row: &FieldValue| -> HashMap<String, FieldValue> {
+                row.as_object()
+                    .map(|fields| fields.clone().into_iter().collect())
+                    .unwrap_or_default()
+            };
+
+            Ok(self
+                .pairs(kind)?
+                .into_iter()
+                .map(|(l, r)| {
+                    merge_rows(
+                        (&self.left_name, l.map(|l| as_hash(&left[l]))),
+                        (&self.right_name, r.map(|r| as_hash(&right[r]))),
+                        &conflicts,
+                        join_key,
+                    )
+                })
+                .collect())
+        }
+    }
+
+    /// separates the name of a Table<T> from the number of one of its pages
+    const PAGE_SEP: char = '@';
+    /// number of pages the records of a Table<T> are spread over
+    const TABLE_PAGES: u64 = 64;
+
+    /// name of page number page of the Table<T> called table: '{table}@{page}'
+    fn page_table(table: &str, page: u64) -> String {
+        format!("{}{}{:02}", table, PAGE_SEP, page)
+    }
+
+    /// true for the pages of a Table<T>, which arent tables of their own
+    fn is_page(name: &str) -> bool {
+        name.contains(PAGE_SEP)
+    }
+
+    /// fails with ReservedName when a table called name would collide with the log,
+    /// the write-ahead log or a manifest, or holds PAGE_SEP without being a page
+    fn check_table_name(name: &str) -> Result<(), TErrors> {
+        let (table, page) = match name.split_once(PAGE_SEP) {
+            Some((table, page)) => (table, Some(page)),
+            None => (name, None),
+        };
+        let numbered =
+            |page: &str| page.len() == 2 && page.parse::<u64>().is_ok_and(|p| p < TABLE_PAGES);
+        if [LOG_TABLE, WAL_TABLE, MANIFEST_TABLE].contains(&table)
+            || page.is_some_and(|p| !numbered(p))
+        {
+            return Err(TErrors::ReservedName);
+        }
+        Ok(())
+    }
+
+    /// what secondary indexes see of the contents of table.
+    /// a page maps keys to records, so its records are indexed as an array
+    fn indexed_rows(table: &str, contents: FieldValue) -> FieldValue {
+        match contents {
+            FieldValue::Object(records) if is_page(table) => {
+                FieldValue::Array(records.into_iter().map(|(_, record)| record).collect())
+            }
+            other => other,
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    /// A table holding many records of type T addressed by a primary key.
+    /// the records are spread over TABLE_PAGES pages by the checksum of their key.
+    /// every page is stored, logged and locked as a table, so a write only rewrites its page
+    pub struct Table<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
+        name: String,
+        db: Database,
+        _records: PhantomData<T>,
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> Table<T> {
+        /// creates new instance. nothing is written until the first insert
+        pub fn new(db: Database, name: String) -> Self {
+            Self {
+                name,
+                db,
+                _records: PhantomData,
+            }
+        }
+
+        /// name of the table
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// name of the page holding key
+        fn page_of(&self, key: &str) -> String {
+            page_table(&self.name, checksum(key.as_bytes()) % TABLE_PAGES)
+        }
+
+        /// names of every page, whether written yet or not
+        fn pages(&self) -> impl Iterator<Item = String> + '_ {
+            (0..TABLE_PAGES).map(|page| page_table(&self.name, page))
+        }
+
+        /// records stored in page. a page never written is empty
+        fn read_page(&self, page: &str) -> Result<BTreeMap<String, T>, TErrors> {
+            let empty: Fragment<BTreeMap<String, T>> = self.db.fragment(BTreeMap::new());
+            match empty.read_table(page.to_string()) {
+                Ok(frag) => Ok(frag.inner),
+                Err(TErrors::FileNotFound) => Ok(BTreeMap::new()),
+                Err(e) => Err(e),
+            }
+        }
+
+        /// writes page back through Fragment.create_table, deleting it once empty
+        fn write_page(&self, page: &str, records: BTreeMap<String, T>) -> Result<(), TErrors> {
+            if records.is_empty() {
+                return self.db.fragment(records).delete_table(page.to_string());
+            }
+            self.db.fragment(records).create_table(page.to_string())?;
+            Ok(())
+        }
+
+        /// reads every record. a table that was never written is empty
+        fn records(&self) -> Result<BTreeMap<String, T>, TErrors> {
+            let mut records: BTreeMap<String, T> = BTreeMap::new();
+            for page in self.pages() {
+                records.extend(self.read_page(&page)?);
+            }
+            Ok(records)
+        }
+
+        /// adds a record. fails with DuplicateKey if the key is taken
+        pub fn insert(&self, key: String, value: T) -> Result<(), TErrors> {
+            let page: String = self.page_of(&key);
+            let _writing = self.db.lock_tables(&[&page])?;
+            let mut records: BTreeMap<String, T> = self.read_page(&page)?;
+            if records.contains_key(&key) {
+                return Err(TErrors::DuplicateKey);
+            }
+            records.insert(key, value);
+            self.write_page(&page, records)
+        }
+
+        /// returns the record stored under key
+        pub fn get(&self, key: &str) -> Result<Option<T>, TErrors> {
+            Ok(self.read_page(&self.page_of(key))?.remove(key))
+        }
+
+        /// version of the record stored under key: the hash of its contents.
+        /// 0 when there is none
+        pub fn version(&self, key: &str) -> Result<u64, TErrors> {
+            Ok(self.get(key)?.as_ref().map(state_hash).unwrap_or_default())
+        }
+
+        /// upsert, failing with Conflict if the record is no longer at version.
+        /// version 0 only adds a record that doesnt exist yet
+        pub fn update_if(&self, key: String, value: T, version: u64) -> Result<Option<T>, TErrors> {
+            let _writing = self.db.lock_tables(&[&self.page_of(&key)])?;
+            if self.version(&key)? != version {
+                return Err(TErrors::Conflict);
+            }
+            self.upsert(key, value)
+        }
+
+        /// adds or replaces a record, returning the one replaced
+        pub fn upsert(&self, key: String, value: T) -> Result<Option<T>, TErrors> {
+            let page: String = self.page_of(&key);
+            let _writing = self.db.lock_tables(&[&page])?;
+            let mut records: BTreeMap<String, T> = self.read_page(&page)?;
+            let previous: Option<T> = records.insert(key, value);
+            self.write_page(&page, records)?;
+            Ok(previous)
+        }
+
+        /// removes a record, returning it if it existed
+        pub fn remove(&self, key: &str) -> Result<Option<T>, TErrors> {
+            let page: String = self.page_of(key);
+            let _writing = self.db.lock_tables(&[&page])?;
+            let mut records: BTreeMap<String, T> = self.read_page(&page)?;
+            let previous: Option<T> = records.remove(key);
+            if previous.is_some() {
+                self.write_page(&page, records)?;
+            }
+            Ok(previous)
+        }
+
+        /// adds a record under its own primary key
+        pub fn insert_keyed<K: ToString>(&self, value: T) -> Result<(), TErrors>
+        where
+            T: Keyed<K>,
+        {
+            self.insert(value.key().to_string(), value)
+        }
+
+        /// adds or replaces a record under its own primary key
+        pub fn upsert_keyed<K: ToString>(&self, value: T) -> Result<Option<T>, TErrors>
+        where
+            T: Keyed<K>,
+        {
+            self.upsert(value.key().to_string(), value)
+        }
+
+        /// generates a primary key for value, stores it on value then inserts it.
+        /// returns the generated key
+        pub fn insert_generated(&self, mut value: T) -> Result<String, TErrors>
+        where
+            T: GeneratedKey,
+        {
+            let key: String = match T::strategy() {
+                KeyStrategy::Uuid => generate_uuid(),
+                KeyStrategy::AutoIncrement => self.next_sequence()?.to_string(),
+            };
+            value.set_key(key.clone());
+            self.insert(key.clone(), value)?;
+            Ok(key)
+        }
+
+        /// bumps and returns the auto increment counter of the table
+        fn next_sequence(&self) -> Result<u64, TErrors> {
+            let _writing = self.db.lock_tables(&[&self.name])?;
+            let seq_name: String = blob_name(&self.name, "seq");
+            let stored: u64 = match self.db.backend.read(&seq_name) {
+                Ok(data) => String::from_utf8_lossy(&data).trim().parse().unwrap_or_default(),
+                Err(TErrors::FileNotFound) => 0,
+                Err(e) => return Err(e),
+            };
+            let highest: u64 = self
+                .records()?
+                .keys()
+                .filter_map(|k| k.parse::<u64>().ok())
+                .max()
+                .unwrap_or_default();
+
+            let next: u64 = stored.max(highest) + 1;
+            self.db.backend.write(&seq_name, next.to_string().as_bytes())?;
+            Ok(next)
+        }
+
+        /// iterates every key and record in key order
+        pub fn iter(&self) -> Result<btree_map::IntoIter<String, T>, TErrors> {
+            Ok(self.records()?.into_iter())
+        }
+
+        /// runs query against the records of the table, returning keys and records.
+        /// only the pages secondary indexes point at are read
+        pub fn query(&self, query: &Query<T>) -> Result<Vec<(String, T)>, TErrors> {
+            let pages: Vec<String> = match query.filter.candidates(&self.db)? {
+                Some(tables) => self.pages().filter(|page| tables.contains(page)).collect(),
+                None => self.pages().collect(),
+            };
+            let mut records: BTreeMap<String, T> = BTreeMap::new();
+            for page in pages {
+                records.extend(self.read_page(&page)?);
+            }
+
+            let mut rows: Vec<(FieldValue, (String, T))> = Vec::new();
+            for (key, record) in records {
+                let Ok(value) = serde_json::to_value(&record) else {
+                    return Err(TErrors::HashConvert);
+                };
+                rows.push((value, (key, record)));
+            }
+            Ok(query.apply(rows))
+        }
+
+        /// number of records in the table
+        pub fn len(&self) -> Result<usize, TErrors> {
+            let mut len: usize = 0;
+            for page in self.pages() {
+                len += self.read_page(&page)?.len();
+            }
+            Ok(len)
+        }
+
+        /// returns true if the table holds no records
+        pub fn is_empty(&self) -> Result<bool, TErrors> {
+            Ok(self.len()? == 0)
+        }
+
+        /// collects every record into a Collection<T>
+        pub fn to_collection(&self) -> Result<Collection<T>, TErrors> {
+            Ok(Collection::new(self.records()?.into_values().collect()))
+        }
+    }
+
+    /// A simple logger for actions done
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct AtomicLogger<T: Serialize + Sized + Clone + Debug + Hash> {
+        /// position in the log, counting from 1. 0 until documented
+        #[serde(default)]
+        pub id: u64,
+        /// kind of operation documented
+        pub op: LogOp,
+        /// table the logged write was made to
+        #[serde(default)]
+        pub table: String,
+        /// hash of the table before the operation
+        pub prior_id: u64,
+        /// hash of the table after the operation. 0 when it no longer exists
+        pub later_id: u64,
+        pub time_stamp: String,
+        pub outcome: Outcome,
+        /// true when the logged write created the table
+        #[serde(default)]
+        pub created: bool,
+        /// the table before the operation. not written to the log,
+        /// Database::log fills it in from the state the entry before left behind
+        #[serde(default, skip_serializing)]
+        pub prior: T,
+        /// the copy written. logged as its state in JSON rather than its bytes
+        #[serde(
+            serialize_with = "serialize_later",
+            deserialize_with = "deserialize_later"
+        )]
+        pub later: Result<AtomicCopy, TErrors>,
+        /// what the operation changed
+        #[serde(default)]
+        pub diff: Diff,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    /// the kind of operation an AtomicLogger documents
+    pub enum LogOp {
+        #[default]
+        Write,
+        Delete,
+        Rollback,
+        Restore,
+        Undo,
+        Redo,
+        Repair,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+    /// Fields added, removed and changed between two versions of a record.
+    /// nested fields are addressed by dotted paths, array items by index, e.g. 'owner.tags.0'.
+    /// a path of "" is the whole record
+    pub struct Diff {
+        /// path and new value of fields only in the new version
+        pub added: BTreeMap<String, FieldValue>,
+        /// path and old value of fields only in the old version
+        pub removed: BTreeMap<String, FieldValue>,
+        /// path, old and new value of fields in both versions that differ
+        pub changed: BTreeMap<String, (FieldValue, FieldValue)>,
+    }
+
+    impl Diff {
+        /// diff between two versions of a record. None means the record doesnt exist
+        pub fn between<A: Serialize, B: Serialize>(old: Option<&A>, new: Option<&B>) -> Self {
+            let old: Option<FieldValue> = old.and_then(|o| serde_json::to_value(o).ok());
+            let new: Option<FieldValue> = new.and_then(|n| serde_json::to_value(n).ok());
+            Self::states(old.as_ref(), new.as_ref())
+        }
+
+        /// diff between two versions already converted to FieldValue
+        pub fn states(old: Option<&FieldValue>, new: Option<&FieldValue>) -> Self {
+            let mut diff: Self = Self::default();
+            match (old, new) {
+                (Some(old), Some(new)) => diff.walk("", old, new),
+                (Some(old), None) => diff.spread("", old, false),
+                (None, Some(new)) => diff.spread("", new, true),
+                (None, None) => {}
+            }
+            diff
+        }
+
+        /// true when nothing changed
+        pub fn is_empty(&self) -> bool {
+            self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+        }
+
+        /// the old version this diff was taken from, given the new version.
+        /// None as new means the record was deleted
+        pub fn revert(&self, new: Option<FieldValue>) -> FieldValue {
+            let Some(mut old) = new else {
+                if let Some(whole) = self.removed.get("") {
+                    return whole.clone();
+                }
+                // every path is a single top level key
+                let fields = self
+                    .removed
+                    .iter()
+                    .map(|(path, v)| (split_path(path).concat(), v.clone()));
+                return FieldValue::Object(fields.collect());
+            };
+
+            for path in self.added.keys() {
+                remove_path(&mut old, path);
+            }
+            let mut restored: Vec<(&String, &FieldValue)> = self
+                .removed
+                .iter()
+                .chain(self.changed.iter().map(|(path, (was, _))| (path, was)))
+                .collect();
+            // array items have to be put back in order, so '10' goes after '9'
+            restored.sort_by_key(|(path, _)| {
+                split_path(path)
+                    .into_iter()
+                    .map(|part| (part.parse::<usize>().ok(), part))
+                    .collect::<Vec<(Option<usize>, String)>>()
+            });
+            for (path, value) in restored {
+                insert_path(&mut old, path, value.clone());
+            }
+            old
+        }
+
+        /// compares old and new found at path
+        fn walk(&mut self, path: &str, old: &FieldValue, new: &FieldValue) {
+            match (old, new) {
+                (FieldValue::Object(old), FieldValue::Object(new)) => {
+                    for (key, old_value) in old {
+                        let field: String = join_path(path, key);
+                        match new.get(key) {
+                            Some(new_value) => self.walk(&field, old_value, new_value),
+                            None => {
+                                self.removed.insert(field, old_value.clone());
+                            }
+                        }
+                    }
+                    for (key, new_value) in new.iter().filter(|(k, _)| !old.contains_key(*k)) {
+                        self.added.insert(join_path(path, key), new_value.clone());
+                    }
+                }
+                (FieldValue::Array(old), FieldValue::Array(new)) => {
+                    for (i, old_value) in old.iter().enumerate() {
+                        let item: String = join_path(path, &i.to_string());
+                        match new.get(i) {
+                            Some(new_value) => self.walk(&item, old_value, new_value),
+                            None => {
+                                self.removed.insert(item, old_value.clone());
+                            }
+                        }
+                    }
+                    for (i, new_value) in new.iter().enumerate().skip(old.len()) {
+                        let item: String = join_path(path, &i.to_string());
+                        self.added.insert(item, new_value.clone());
+                    }
+                }
+                (old, new) if old != new => {
+                    self.changed.insert(path.to_string(), (old.clone(), new.clone()));
+                }
+                _ => {}
+            }
+        }
+
+        /// lists every top level field of a record that appeared or disappeared
+        fn spread(&mut self, path: &str, value: &FieldValue, added: bool) {
+            let side: &mut BTreeMap<String, FieldValue> = if added {
+                &mut self.added
+            } else {
+                &mut self.removed
+            };
+            match value {
+                FieldValue::Object(fields) => {
+                    for (key, field) in fields {
+                        side.insert(join_path(path, key), field.clone());
+                    }
+                }
+                value => {
+                    side.insert(path.to_string(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// removes the field at a dotted path. an array is cut short at the item
+    fn remove_path(value: &mut FieldValue, path: &str) {
+        let mut parts: Vec<String> = split_path(path);
+        let last: String = parts.pop().unwrap_or_default();
+        match parent_at(value, &parts) {
+            Some(FieldValue::Object(fields)) => {
+                fields.remove(&last);
+            }
+            Some(FieldValue::Array(items)) => {
+                if let Ok(i) = last.parse::<usize>() {
+                    items.truncate(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// sets the field at a dotted path, "" being the whole value
+    fn insert_path(value: &mut FieldValue, path: &str, field: FieldValue) {
+        if path.is_empty() {
+            *value = field;
+            return;
+        }
+        let mut parts: Vec<String> = split_path(path);
+        let last: String = parts.pop().unwrap_or_default();
+        match parent_at(value, &parts) {
+            Some(FieldValue::Object(fields)) => {
+                fields.insert(last, field);
+            }
+            Some(FieldValue::Array(items)) => {
+                let Ok(i) = last.parse::<usize>() else {
+                    return;
+                };
+                if i >= items.len() {
+                    items.resize(i + 1, FieldValue::Null);
+                }
+                items[i] = field;
+            }
+            _ => {}
+        }
+    }
+
+    /// the object or array found by following parts
+    fn parent_at<'a>(value: &'a mut FieldValue, parts: &[String]) -> Option<&'a mut FieldValue> {
+        parts.iter().try_fold(value, |value, part| match value {
+            FieldValue::Object(fields) => fields.get_mut(part),
+            FieldValue::Array(items) => items.get_mut(part.parse::<usize>().ok()?),
+            _ => None,
+        })
+    }
+
+    /// appends key to a dotted path. a '.' or backslash in key is escaped with a backslash.
+    /// an empty key is a lone backslash, so it isnt taken for the whole value
+    fn join_path(path: &str, key: &str) -> String {
+        let key: String = match key {
+            "" => "\\".to_string(),
+            key => key.replace('\\', "\\\\").replace('.', "\\."),
+        };
+        if path.is_empty() {
+            key
+        } else {
+            format!("{}.{}", path, key)
+        }
+    }
+
+    /// the keys of a dotted path made by join_path
+    fn split_path(path: &str) -> Vec<String> {
+        let mut parts: Vec<String> = Vec::new();
+        let mut part: String = String::new();
+        let mut chars = path.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => part.extend(chars.next()),
+                '.' => parts.push(std::mem::take(&mut part)),
+                c => part.push(c),
+            }
+        }
+        parts.push(part);
+        parts
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    /// a moment in the operation log to restore to
+    pub enum RestorePoint {
+        /// right after the last entry stamped at or before the time
+        Time(SystemTime),
+        /// right after the entry with this log id
+        LogId(u64),
+    }
+
+    impl RestorePoint {
+        /// true when entry was logged after the point
+        fn is_before(&self, entry: &FieldValue) -> bool {
+            match self {
+                Self::Time(time) => entry
+                    .get("time_stamp")
+                    .and_then(|t| t.as_str())
+                    .is_some_and(|t| t > rfc3339(*time).as_str()),
+                Self::LogId(id) => entry
+                    .get("id")
+                    .and_then(|i| i.as_u64())
+                    .is_some_and(|i| i > *id),
+            }
+        }
+    }
+
+    impl From<SystemTime> for RestorePoint {
+        fn from(time: SystemTime) -> Self {
+            Self::Time(time)
+        }
+    }
+
+    impl From<u64> for RestorePoint {
+        fn from(id: u64) -> Self {
+            Self::LogId(id)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    /// whether a documented operation went through
+    pub enum Outcome {
+        #[default]
+        Success,
+        Failure,
+    }
+
+    impl Outcome {
+        /// Success for Ok, Failure for Err
+        pub fn of<R>(result: &Result<R, TErrors>) -> Self {
+            match result {
+                Ok(_) => Self::Success,
+                Err(_) => Self::Failure,
+            }
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> AtomicLogger<T> {
+        /// hash of the state held by later. 0 when it holds none
+        fn state_id(later: &AtomicCopy) -> u64 {
+            copy_hash(later)
+        }
+    }
+
+    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> ToLogAtomic<T>
+        for AtomicLogger<T>
+    {
+        fn new(prior: T, later: AtomicCopy, time_stamp: String) -> Self {
+            Self {
+                id: 0,
+                op: LogOp::Write,
+                table: later.title.clone(),
+                prior_id: state_hash(&prior), 
+                later_id: Self::state_id(&later), 
+                time_stamp,
+                outcome: Outcome::Success,
+                created: false,
+                diff: Diff::between(
+                    Some(&prior),
+                    Format::decode::<FieldValue>(&later.data).ok().as_ref(),
+                ),
+                prior,
+                later: Ok(later), 
+            }
+        }
+
+        fn set_hash_ids(&self) -> Result<Self, TErrors> {
+            Ok(Self {
+                prior_id: state_hash(&self.prior),
+                later_id: Self::state_id(&self.later.clone()?),
+                table: self.table.clone(),
+                created: self.created,
+                id: self.id,
+                op: self.op,
+                outcome: self.outcome,
+                diff: self.diff.clone(),
+                prior: self.prior.clone(),
+                later: self.later.clone(),
+                time_stamp: self.time_stamp.clone(),
+            })
+        }
+
+        fn compare_ids(&self) -> bool {
+            self.prior_id == self.later_id
+        }
+
+        fn document(&self, db: &Database) -> Result<(), TErrors> {
+            let Ok(entry) = serde_json::to_value(self.clone()) else {
+                return Err(TErrors::StringConvert);
+            };
+            append_logs(db, vec![entry])?;
+            Ok(())
+        }
+
+        fn set_prior(&self, prior: T) -> Self {
+            Self {
+                prior_id: self.prior_id,
+                later_id: self.later_id,
+                table: self.table.clone(),
+                created: self.created,
+                id: self.id,
+                op: self.op,
+                outcome: self.outcome,
+                diff: self.diff.clone(),
+                prior: prior.clone(),
+                later: self.later.clone(),
+                time_stamp: self.time_stamp.clone(),
+            }
+        }
+
+        fn set_later(&self, later: AtomicCopy) -> Self {
+            Self {
+                prior_id: self.prior_id,
+                later_id: self.later_id,
+                table: self.table.clone(),
+                created: self.created,
+                id: self.id,
+                op: self.op,
+                outcome: Outcome::Success,
+                diff: self.diff.clone(),
+                prior: self.prior.clone(),
+                later: Ok(later.clone()),
+                time_stamp: self.time_stamp.clone(),
+            }
+        }
+
+        fn set_time_stamp(&self, time_stamp: String) -> Self {
+            Self {
+                prior_id: self.prior_id,
+                later_id: self.later_id,
+                table: self.table.clone(),
+                created: self.created,
+                id: self.id,
+                op: self.op,
+                outcome: self.outcome,
+                diff: self.diff.clone(),
+                prior: self.prior.clone(),
+                later: self.later.clone(),
+                time_stamp: time_stamp.clone(),
+            }
+        }
+
+        fn diff(&self) -> Result<Diff, TErrors> {
+            let later: AtomicCopy = self.later.clone()?;
+            let prior: Option<FieldValue> = if self.created {
+                None
+            } else {
+                serde_json::to_value(&self.prior).ok()
+            };
+            let later: Option<FieldValue> = if later.data.is_empty() {
+                None
+            } else {
+                Some(Format::decode::<FieldValue>(&later.data)?)
+            };
+            Ok(Diff::states(prior.as_ref(), later.as_ref()))
+        }
+
+        fn is_success(&self) -> Result<bool, TErrors> {
+            Ok(self.commit()?.success)
+        }
+
+        fn is_failure(&self) -> Result<bool, TErrors> {
+            Ok(!self.commit()?.success)
+        }
+
+        fn commit(&self) -> Result<Commit<AtomicCopy>, TErrors> {
+            self.set_hash_ids()?;
+            Ok(Commit::default().determine(self.later.clone(), Err(TErrors::None)))
+        }
+
+        fn rollback(&self, db: &Database) -> Result<AtomicLogger<T>, TErrors> {
+            let table: String = match &self.later {
+                Ok(later) if self.table.is_empty() => later.title.clone(),
+                _ => self.table.clone(),
+            };
+            if table.is_empty() {
+                return Err(TErrors::FileNotFound);
+            }
+            let _writing = db.lock_tables(&[&table])?;
+
+            let stored: Option<Vec<u8>> = db.stored_blob(&table);
+            let current: Option<T> = stored
+                .as_ref()
+                .and_then(|buf| Format::decode::<T>(buf).ok());
+            let format: Format = db.table_format(&table);
+
+            let restored: AtomicCopy = if self.created {
+                db.delete_blob(&table)?;
+                db.reindex_table(&table, None)?;
+                AtomicCopy::new(table.clone(), format.ext().to_string(), Vec::new())
+            } else {
+                let restored: AtomicCopy = AtomicCopy::new(
+                    table.clone(),
+                    format.ext().to_string(),
+                    format.encode(&self.prior)?,
+                );
+                let restored: AtomicCopy = db.write_ahead(&restored)?.check(db)?;
+
+                // drop the copy left behind if the table switched formats
+                for other in Format::ALL.into_iter().filter(|f| *f != format) {
+                    db.backend.delete(&blob_name(&table, other.ext()))?;
+                }
+                restored
+            };
+
+            let mut rolled_back: AtomicLogger<T> = AtomicLogger {
+                id: 0,
+                op: LogOp::Rollback,
+                table,
+                prior_id: stored_hash(stored.as_deref()),
+                later_id: Self::state_id(&restored),
+                time_stamp: db.time_stamp(),
+                outcome: Outcome::Success,
+                created: stored.is_none(),
+                diff: Diff::between(current.as_ref(), (!self.created).then_some(&self.prior)),
+                prior: current.unwrap_or_default(),
+                later: Ok(restored),
+            };
+            let Ok(entry) = serde_json::to_value(rolled_back.clone()) else {
+                return Err(TErrors::StringConvert);
+            };
+            rolled_back.id = append_logs(db, vec![entry])?;
+            Ok(rolled_back)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use serde_json::json;
+
+        #[derive(Serialize, Deserialize, Clone, Debug, Hash, Default, PartialEq)]
+        struct Account {
+            name: String,
+            balance: i64,
+        }
+
+        fn account(name: &str, balance: i64) -> Account {
+            Account {
+                name: name.to_string(),
+                balance,
+            }
+        }
+
+        #[derive(Debug)]
+        /// memory backend failing the first rename onto one blob
+        struct FailingRename {
+            inner: MemoryBackend,
+            target: Mutex<String>,
+        }
+
+        impl StorageBackend for FailingRename {
+            fn read(&self, name: &str) -> Result<Vec<u8>, TErrors> {
+                self.inner.read(name)
+            }
+            fn write(&self, name: &str, data: &[u8]) -> Result<(), TErrors> {
+                self.inner.write(name, data)
+            }
+            fn rename(&self, from: &str, to: &str) -> Result<(), TErrors> {
+                let mut target = self.target.lock().unwrap();
+                if to == *target {
+                    target.clear();
+                    return Err(TErrors::FileError);
+                }
+                self.inner.rename(from, to)
+            }
+            fn list(&self) -> Result<Vec<String>, TErrors> {
+                self.inner.list()
+            }
+            fn delete(&self, name: &str) -> Result<(), TErrors> {
+                self.inner.delete(name)
+            }
+            fn exists(&self, name: &str) -> bool {
+                self.inner.exists(name)
+            }
+        }
+
+        /// the record stored in table
+        fn read(db: &Database, table: &str) -> Option<Account> {
+            db.fragment(Account::default())
+                .read_table(table.to_string())
+                .ok()
+                .map(|f| f.inner)
+        }
+
+        /// true when no temp file is left in db
+        fn no_temps(db: &Database) -> bool {
+            db.backend()
+                .list()
+                .unwrap()
+                .iter()
+                .all(|name| !name.ends_with(".temp"))
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, Hash, Default, PartialEq)]
+        struct Profile {
+            name: String,
+            age: u32,
+            active: bool,
+            nickname: Option<String>,
+            tags: Vec<String>,
+            owner: Account,
+        }
 
-    impl ToHashOpt for HashMap<String, String> {
-        /// impl of ToHashOpt.convert_opt()
-        fn convert_opt(&self) -> HashMap<String, Option<String>> {
-            let mut temp_hash: HashMap<String, Option<String>> = HashMap::new();
+        fn profile() -> Profile {
+            Profile {
+                name: "ada".to_string(),
+                age: 36,
+                active: true,
+                nickname: None,
+                tags: vec!["math".to_string()],
+                owner: account("bob", 5),
+            }
+        }
 
-            self.into_iter().for_each(|(k, v)| {
-                let v: Option<String> = if v.len() > 0 { Some(v.clone()) } else { None };
+        #[test]
+        fn to_hash_keeps_the_type_of_every_field() {
+            let hashed: HashMap<String, FieldValue> =
+                Database::in_memory().fragment(profile()).to_hash().unwrap();
+            assert_eq!(hashed["name"], json!("ada"));
+            assert_eq!(hashed["age"], json!(36));
+            assert_eq!(hashed["active"], json!(true));
+            assert_eq!(hashed["nickname"], FieldValue::Null);
+            assert_eq!(hashed["tags"], json!(["math"]));
+            assert_eq!(hashed["owner"], json!({"name": "bob", "balance": 5}));
 
-                temp_hash.insert(k.clone(), v);
+            let optional: HashMap<String, Option<FieldValue>> = Database::in_memory()
+                .fragment(profile())
+                .to_hash_opt()
+                .unwrap();
+            assert_eq!(optional["nickname"], None);
+            assert_eq!(optional["age"], Some(json!(36)));
+        }
+
+        #[test]
+        fn typed_updates_are_stored_and_read_back_typed() {
+            let db: Database = Database::in_memory();
+            let ada: Fragment<Profile> = db.fragment(profile());
+            ada.create_table("ada".to_string()).unwrap();
+
+            let older: Profile = ada
+                .update_table("ada".to_string(), "age".to_string(), json!(37))
+                .unwrap();
+            assert_eq!(older.age, 37);
+            let named: Profile = ada
+                .update_table("ada".to_string(), "nickname".to_string(), json!("countess"))
+                .unwrap();
+            assert_eq!(named.nickname.as_deref(), Some("countess"));
+            // a value of the wrong type is refused rather than stored
+            assert!(
+                ada.update_table("ada".to_string(), "age".to_string(), json!("old"))
+                    .is_err()
+            );
+
+            let stored: Vec<HashMap<String, FieldValue>> = ada.get_all().unwrap();
+            assert_eq!(stored.len(), 1);
+            assert_eq!(stored[0]["age"], json!(37));
+            assert_eq!(stored[0]["active"], json!(true));
+        }
+
+        #[test]
+        fn file_and_memory_backends_store_blobs_alike() {
+            let dir: PathBuf = scratch_dir("backends");
+            let file: FileBackend = FileBackend::new(&dir);
+            let memory: MemoryBackend = MemoryBackend::new();
+            let backends: [&dyn StorageBackend; 2] = [&file, &memory];
+            for backend in backends {
+                assert!(matches!(backend.read("a"), Err(TErrors::FileNotFound)));
+                backend.write("a", b"one").unwrap();
+                backend.write("a", b"two").unwrap();
+                assert_eq!(backend.read("a").unwrap(), b"two");
+
+                backend.append("a", b"!").unwrap();
+                backend.append("b", b"new").unwrap();
+                assert_eq!(backend.read("a").unwrap(), b"two!");
+                assert_eq!(backend.read("b").unwrap(), b"new");
+
+                backend.rename("a", "c").unwrap();
+                assert!(!backend.exists("a") && backend.exists("c"));
+                assert!(backend.rename("missing", "d").is_err());
+                let mut names: Vec<String> = backend.list().unwrap();
+                names.sort();
+                assert_eq!(names, vec!["b".to_string(), "c".to_string()]);
+
+                backend.delete("c").unwrap();
+                backend.delete("c").unwrap();
+                assert!(!backend.exists("c"));
+            }
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn tables_work_the_same_on_any_backend() {
+            let dir: PathBuf = scratch_dir("any-backend");
+            for db in [Database::new(FileBackend::new(&dir)), Database::in_memory()] {
+                write(&db, "ada", account("ada", 10));
+                write(&db, "ada", account("ada", 20));
+                assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+                assert_eq!(db.tables().unwrap(), vec!["ada".to_string()]);
+                db.fragment(Account::default())
+                    .delete_table("ada".to_string())
+                    .unwrap();
+                assert_eq!(read(&db, "ada"), None);
+                assert_eq!(db.log::<Account>("ada").unwrap().len(), 3);
+            }
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn binary_round_trips_every_kind_of_value() {
+            let value: FieldValue = json!({
+                "numbers": [0, 1, -7, u64::MAX, i64::MIN, 2.5, -0.125],
+                "text": ["", "plain", "ünïcödé"],
+                "flags": [true, false, null],
+                "nested": {"empty": {}, "list": [[], [{"a": 1}]]},
             });
+            for format in Format::ALL {
+                let data: Vec<u8> = format.encode(&value).unwrap();
+                assert_eq!(Format::detect(&data), format);
+                assert_eq!(Format::decode::<FieldValue>(&data).unwrap(), value);
+            }
+        }
 
-            temp_hash
+        #[test]
+        fn binary_rejects_truncated_and_padded_input() {
+            let data: Vec<u8> = Format::Binary.encode(&account("ada", 10)).unwrap();
+            for len in 0..data.len() {
+                assert!(
+                    Format::decode::<FieldValue>(&data[..len]).is_err(),
+                    "cut at {len}"
+                );
+            }
+
+            let mut padded: Vec<u8> = data.clone();
+            padded.push(0);
+            assert!(Format::decode::<FieldValue>(&padded).is_err());
+
+            let mut newer: Vec<u8> = data;
+            newer[BINARY_MAGIC.len()] = BINARY_VERSION + 1;
+            assert!(matches!(
+                Format::decode::<FieldValue>(&newer),
+                Err(TErrors::FormatError)
+            ));
         }
-    }
 
-    /// added functionality to count iterations of an item in a vector
-    /// of a generic type
-    pub fn count_val<T: std::fmt::Debug + Eq>(vec: Vec<T>, value: String) -> usize {
-        vec.into_iter()
-            .map(|c| format!("{c:?}").trim().to_string())
-            .filter(|v| *v == value.trim().to_string())
-            .collect::<Vec<String>>()
-            .len()
-    }
+        /// a binary file holding payload, with a header of the current version
+        fn binary_file(payload: &[u8]) -> Vec<u8> {
+            let mut data: Vec<u8> = BINARY_MAGIC.to_vec();
+            data.push(BINARY_VERSION);
+            data.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+            data.extend_from_slice(payload);
+            data
+        }
 
-    #[derive(Debug, Serialize)]
-    /// Takes in a struct of type T,
-    /// then applies concept_db functionality
-    pub struct Fragment<T: Serialize + DeserializeOwned + Sized + Clone> {
-        pub inner: T,
-    }
+        #[test]
+        fn binary_rejects_bytes_left_after_the_value() {
+            // null, then a stray null inside the declared payload length
+            assert!(Format::decode::<FieldValue>(&binary_file(&[0])).is_ok());
+            assert!(matches!(
+                Format::decode::<FieldValue>(&binary_file(&[0, 0])),
+                Err(TErrors::FormatError)
+            ));
+            assert!(Format::decode::<FieldValue>(b"{} {}").is_err());
+        }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + From<AtomicCopy> + Default> ToString for Fragment<T> 
-    where 
-        AtomicLogger<T>: Default + DeserializeOwned + Hash,
-        Collection<AtomicLogger<T>>: Clone + Debug + Default + DeserializeOwned + Hash + Serialize,
-        T: From<AtomicCopy> + Default, 
-    {
-        /// converts T to String
-        fn to_string(&self) -> String {
-            let Ok(output) = to_string(&self.inner) else {
-                panic!("unable to convert: {self:?}");
+        #[test]
+        fn binary_limits_how_deep_values_nest() {
+            // arrays of one item, nested depth times around a null
+            let nested = |depth: usize| {
+                let mut payload: Vec<u8> = [7, 1].repeat(depth);
+                payload.push(0);
+                binary_file(&payload)
             };
+            assert!(Format::decode::<FieldValue>(&nested(BINARY_MAX_DEPTH)).is_ok());
+            assert!(matches!(
+                Format::decode::<FieldValue>(&nested(BINARY_MAX_DEPTH + 1)),
+                Err(TErrors::FormatError)
+            ));
+            assert!(Format::decode::<FieldValue>(&nested(100_000)).is_err());
+        }
 
-            output
+        #[test]
+        fn table_format_is_detected_on_read_and_switching_drops_the_old_file() {
+            let db: Database =
+                Database::in_memory().set_table_format("accounts".to_string(), Format::Binary);
+            db.fragment(account("ada", 10))
+                .create_table("accounts".to_string())
+                .unwrap();
+            assert!(db.backend().exists("accounts.bin"));
+
+            let json: Database = db.set_table_format("accounts".to_string(), Format::Json);
+            let read: Fragment<Account> = json
+                .fragment(Account::default())
+                .read_table("accounts".to_string())
+                .unwrap();
+            assert_eq!(read.inner, account("ada", 10));
+
+            json.fragment(account("ada", 20))
+                .create_table("accounts".to_string())
+                .unwrap();
+            assert!(db.backend().exists("accounts.json"));
+            assert!(!db.backend().exists("accounts.bin"));
         }
-    }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug> ToHash for Fragment<T> 
-    where 
-        Fragment<T>: Display + ToString
-    {
-        /// converts T to HashMap<String, String>
-        fn to_hash(&self) -> Result<HashMap<String, String>, TErrors>
-        where
-            HashMap<String, String>: Serialize,
-        {
-            let Ok(output) = serde_json::from_str::<HashMap<String, String>>(&self.to_string())
-            else {
-                return Err(TErrors::StringConvert);
-            };
+        #[test]
+        fn table_inserts_gets_upserts_removes_and_iterates_records() {
+            let accounts: Table<Account> = Database::in_memory().table("accounts".to_string());
+            assert!(accounts.is_empty().unwrap());
+            accounts
+                .insert("bob".to_string(), account("bob", 5))
+                .unwrap();
+            accounts
+                .insert("ada".to_string(), account("ada", 10))
+                .unwrap();
+            assert!(matches!(
+                accounts.insert("ada".to_string(), account("ada", 0)),
+                Err(TErrors::DuplicateKey)
+            ));
 
-            Ok(output)
+            assert_eq!(accounts.get("ada").unwrap(), Some(account("ada", 10)));
+            assert_eq!(accounts.get("cat").unwrap(), None);
+            let replaced: Option<Account> = accounts
+                .upsert("ada".to_string(), account("ada", 20))
+                .unwrap();
+            assert_eq!(replaced, Some(account("ada", 10)));
+            let added: Option<Account> = accounts
+                .upsert("cat".to_string(), account("cat", 1))
+                .unwrap();
+            assert_eq!(added, None);
+
+            assert_eq!(accounts.remove("bob").unwrap(), Some(account("bob", 5)));
+            assert_eq!(accounts.remove("bob").unwrap(), None);
+            let records: Vec<(String, Account)> = accounts.iter().unwrap().collect();
+            assert_eq!(
+                records,
+                vec![
+                    ("ada".to_string(), account("ada", 20)),
+                    ("cat".to_string(), account("cat", 1)),
+                ]
+            );
+            assert_eq!(accounts.len().unwrap(), 2);
         }
-        /// converts T to HashMap<String, Option<String>>
-        fn to_hash_opt(&self) -> Result<HashMap<String, Option<String>>, TErrors>
-        where
-            HashMap<String, Option<String>>: Serialize,
-        {
-            let Ok(output) = self.to_hash() else {
-                return Err(TErrors::HashConvert);
-            };
 
-            Ok(output.convert_opt())
+        #[test]
+        fn table_keys_may_hold_any_character() {
+            let accounts: Table<u64> = Database::in_memory().table("accounts".to_string());
+            let keys: [&str; 9] = ["Rex", "rex", "a/b", "../up", "100%", "k@01", "ünï", " ", ""];
+            for (i, key) in keys.iter().enumerate() {
+                accounts.insert(key.to_string(), i as u64).unwrap();
+            }
+            for (i, key) in keys.iter().enumerate() {
+                assert_eq!(accounts.get(key).unwrap(), Some(i as u64), "key {key:?}");
+            }
+            let mut sorted: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+            sorted.sort();
+            let stored: Vec<String> = accounts.iter().unwrap().map(|(k, _)| k).collect();
+            assert_eq!(stored, sorted);
         }
 
-        /// converts T to HashMap<String, Vec<String>>
-        fn to_hash_vec(&self) -> Result<HashMap<String, Vec<String>>, TErrors> {
-            let Ok(output) =
-                serde_json::from_str::<HashMap<String, Vec<String>>>(&self.to_string())
-            else {
-                return Err(TErrors::StringConvert);
+        #[test]
+        fn table_records_share_a_few_pages_kept_out_of_tables() {
+            let db: Database = Database::in_memory();
+            let accounts: Table<u64> = db.table("accounts".to_string());
+            for i in 0..500 {
+                accounts.insert(format!("k{i}"), i).unwrap();
+            }
+            write(&db, "owner", account("ada", 10));
+
+            assert_eq!(accounts.len().unwrap(), 500);
+            assert_eq!(db.tables().unwrap(), vec!["owner".to_string()]);
+            let files: usize = db.backend().list().unwrap().len();
+            assert!(files <= 2 * TABLE_PAGES as usize + 8, "{files} files");
+            let everything = db.fragment(Account::default()).get_all_infer().unwrap();
+            assert_eq!(everything.len(), 1);
+            assert!(db.verify().unwrap().is_clean());
+        }
+
+        /// owners keyed by id and the pets pointing at them. ada has two pets,
+        /// cy none and the pet of owner 4 has no owner
+        fn owners_and_pets() -> (Vec<FieldValue>, Vec<FieldValue>) {
+            let owners: Vec<FieldValue> = vec![
+                json!({"id": 1, "name": "ada"}),
+                json!({"id": 2, "name": "bob"}),
+                json!({"id": 3, "name": "cy"}),
+                json!({"id": null, "name": "nobody"}),
+            ];
+            let pets: Vec<FieldValue> = vec![
+                json!({"owner": 1, "name": "rex"}),
+                json!({"owner": 2, "name": "tom"}),
+                json!({"owner": 1, "name": "fig"}),
+                json!({"owner": 4, "name": "stray"}),
+                json!({"owner": null, "name": "wild"}),
+            ];
+            (owners, pets)
+        }
+
+        /// the names of both sides of every pair, "-" for a missing side
+        fn names(pairs: Vec<JoinPair<FieldValue, FieldValue>>) -> Vec<(String, String)> {
+            let name = |row: Option<FieldValue>| match row {
+                Some(row) => row["name"].as_str().unwrap().to_string(),
+                None => "-".to_string(),
             };
+            pairs.into_iter().map(|(l, r)| (name(l), name(r))).collect()
+        }
 
-            Ok(output)
+        /// names of the pairs of an inner join
+        fn inner_names(join: &Join<FieldValue, FieldValue>) -> Vec<(String, String)> {
+            let pairs = join.inner().unwrap().into_iter();
+            names(pairs.map(|(l, r)| (Some(l), Some(r))).collect())
         }
 
-        fn zip(&self) -> Result<Vec<(String, String)>, TErrors> {
-            let mut _temp_vec: Vec<(String, String)> = Vec::new();
-            if let Ok(vec_hash) = self.to_hash() {
-                let keys = vec_hash.clone().into_keys().collect::<Vec<String>>();
-                let values = vec_hash.into_values().collect::<Vec<String>>();
-                _temp_vec = keys.iter().map(|d| d.clone()).zip(values).collect();
-                return Ok(_temp_vec);
-            } else {
-                println!("This is an error still in work. Try raw_changes_collect.");
-                return Err(TErrors::HashConvert);
+        fn pair(left: &str, right: &str) -> (String, String) {
+            (left.to_string(), right.to_string())
+        }
+
+        #[test]
+        fn joins_pair_every_match_on_both_sides() {
+            let (owners, pets) = owners_and_pets();
+            let join: Join<FieldValue, FieldValue> = Join::new(owners, pets, "id", "owner");
+
+            let inner: Vec<(String, String)> = inner_names(&join);
+            let matched = vec![pair("ada", "rex"), pair("ada", "fig"), pair("bob", "tom")];
+            assert_eq!(inner, matched);
+
+            let left = join.left().unwrap().into_iter();
+            let left = names(left.map(|(l, r)| (Some(l), r)).collect());
+            assert_eq!(left.len(), 5);
+            assert!(left.contains(&pair("cy", "-")) && left.contains(&pair("nobody", "-")));
+
+            let right = join.right().unwrap().into_iter();
+            let right = names(right.map(|(l, r)| (l, Some(r))).collect());
+            assert_eq!(right.len(), 5);
+            assert!(right.contains(&pair("-", "stray")) && right.contains(&pair("-", "wild")));
+
+            let full: Vec<(String, String)> = names(join.full().unwrap());
+            assert_eq!(full.len(), 7);
+            for expected in left.iter().chain(&right) {
+                assert!(full.contains(expected), "{expected:?}");
             }
         }
-    }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default + From<AtomicCopy>> Fragment<T> 
-    where 
-        Fragment<T>: Display + ToString,
-        AtomicLogger<T>: Default + DeserializeOwned + Hash, 
-        Collection<AtomicLogger<T>>: Clone + Debug + Default + DeserializeOwned + Hash + Serialize + From<Vec<u8>>, 
-        Collection<T>: Clone + Debug + Default + DeserializeOwned + Hash + Serialize
-    {
-        /// initializes the Fragment<T>
-        pub fn new(inner: T) -> Self
-        where
-            T: Serialize + DeserializeOwned + Sized + Clone,
-        {
-            Self { inner }
+        #[test]
+        fn join_keys_match_after_trimming_but_not_across_types() {
+            let left: Vec<FieldValue> = vec![
+                json!({"k": " a ", "name": "l1"}),
+                json!({"k": 1, "name": "l2"}),
+            ];
+            let right: Vec<FieldValue> = vec![
+                json!({"k": "a", "name": "r1"}),
+                json!({"k": "1", "name": "r2"}),
+            ];
+            let join: Join<FieldValue, FieldValue> = Join::new(left, right, "k", "k");
+            assert_eq!(inner_names(&join), vec![pair("l1", "r1")]);
         }
 
-        /// parses file if exists then returns a new Fragment<T>
-        pub fn read_table(&self, file_path: String) -> Result<Fragment<T>, TErrors> {
-            let path: String = format!("./db_files/{}.json", file_path);
+        #[test]
+        fn merged_rows_prefix_conflicting_columns_and_keep_the_key_once() {
+            let left: Vec<FieldValue> = vec![json!({"id": 1, "name": "ada"})];
+            let right: Vec<FieldValue> = vec![json!({"id": 1, "name": "rex", "age": 3})];
+            let join = Join::new(left, right, "id", "id").set_names("owners", "pets");
 
-            let convert_path: &Path = Path::new(&path);
+            let merged: Vec<HashMap<String, FieldValue>> = join.merged(JoinKind::Inner).unwrap();
+            let expected: HashMap<String, FieldValue> = HashMap::from([
+                ("id".to_string(), json!(1)),
+                ("owners.name".to_string(), json!("ada")),
+                ("pets.name".to_string(), json!("rex")),
+                ("age".to_string(), json!(3)),
+            ]);
+            assert_eq!(merged, vec![expected]);
+        }
 
-            if !convert_path.exists() {
-                return Err(TErrors::FileNotFound);
+        #[test]
+        fn tables_join_on_their_records() {
+            let db: Database = Database::in_memory();
+            let owners: Table<FieldValue> = db.table("owners".to_string());
+            let pets: Table<FieldValue> = db.table("pets".to_string());
+            let (owner_rows, pet_rows) = owners_and_pets();
+            for (i, row) in owner_rows.into_iter().enumerate() {
+                owners.insert(i.to_string(), row).unwrap();
+            }
+            for (i, row) in pet_rows.into_iter().enumerate() {
+                pets.insert(i.to_string(), row).unwrap();
             }
 
-            let Ok(f) = File::open(convert_path) else {
-                println!("Failed to read file under 'read_table'");
-                return Err(TErrors::FileError);
-            };
+            let join = Join::tables(&owners, &pets, "id", "owner").unwrap();
+            let mut inner: Vec<(String, String)> = inner_names(&join);
+            inner.sort();
+            let matched = vec![pair("ada", "fig"), pair("ada", "rex"), pair("bob", "tom")];
+            assert_eq!(inner, matched);
 
-            let reader: BufReader<File> = BufReader::new(f);
+            let merged: Vec<HashMap<String, FieldValue>> = join.merged(JoinKind::Full).unwrap();
+            assert_eq!(merged.len(), 7);
+            let cy: Option<&FieldValue> = Some(&json!("cy"));
+            assert!(merged.iter().any(|row| row.get("owners.name") == cy));
+        }
 
-            let Ok(inner_value) = serde_json::from_reader(reader) else {
-                return Err(TErrors::ReadByteError);
-            };
+        impl Keyed<String> for Account {
+            fn key(&self) -> String {
+                self.name.clone()
+            }
+        }
 
-            let new_value: Fragment<T> = Self::new(inner_value);
+        #[derive(Serialize, Deserialize, Clone, Debug, Hash, Default, PartialEq)]
+        struct Ticket {
+            id: String,
+            title: String,
+        }
 
-            Ok(new_value)
+        impl GeneratedKey for Ticket {
+            fn strategy() -> KeyStrategy {
+                KeyStrategy::AutoIncrement
+            }
+            fn set_key(&mut self, key: String) {
+                self.id = key;
+            }
         }
 
-        /// creates a new json file and inputs the table
-        /// created using the struct
-        pub fn create_table(&self, table_name: String) -> Result<&Self, TErrors> {
-            let path_root: &String = &String::from("./db_files/");
+        #[derive(Serialize, Deserialize, Clone, Debug, Hash, Default, PartialEq)]
+        struct Session {
+            id: String,
+        }
 
-            if !Path::new(path_root).exists() {
-                std::fs::create_dir(path_root).map_err(|_| {
-                    println!("Error creating the directory: './db_files/'");
-                    return TErrors::DirError;
-                })?;
+        impl GeneratedKey for Session {
+            fn strategy() -> KeyStrategy {
+                KeyStrategy::Uuid
             }
+            fn set_key(&mut self, key: String) {
+                self.id = key;
+            }
+        }
 
-            let string_convert: String = self.to_string();
+        #[test]
+        fn fragments_are_found_updated_and_deduplicated_by_key() {
+            let db: Database = Database::in_memory();
+            write(&db, "first", account("ada", 10));
+            write(&db, "second", account("bob", 5));
+            write(&db, "third", account("ada", 99));
 
-            let atom: AtomicCopy = AtomicCopy::new(table_name.clone(), "json".to_string(), string_convert);
+            let ada: Fragment<Account> = db.fragment(account("ada", 20));
+            let (table, found) = ada.get_by_key(&"ada".to_string()).unwrap().unwrap();
+            assert_eq!((table.as_str(), found.inner), ("first", account("ada", 10)));
+            assert!(ada.get_by_key(&"cy".to_string()).unwrap().is_none());
 
-            let prior = self.read_table(format!("{}{}.{}", path_root, table_name, "json"))?.inner;
+            assert_eq!(ada.update_by_key().unwrap(), "first");
+            assert_eq!(read(&db, "first"), Some(account("ada", 20)));
+            let cy: Fragment<Account> = db.fragment(account("cy", 0));
+            assert!(matches!(cy.update_by_key(), Err(TErrors::FileNotFound)));
 
+            assert_eq!(ada.dedupe_by_key().unwrap(), vec!["third".to_string()]);
+            assert_eq!(db.tables().unwrap(), vec!["first", "second"]);
+        }
 
-            let atomic_logger = AtomicLogger {
-                prior_id: write_hash(prior.clone()),
-                later_id: write_hash(atom.clone()),
-                prior, 
-                later: atom.construct()?.replace(), 
-                time_stamp: "12:00".to_string()
-            }; 
+        #[test]
+        fn records_are_stored_under_their_own_or_a_generated_key() {
+            let db: Database = Database::in_memory();
+            let accounts: Table<Account> = db.table("accounts".to_string());
+            accounts.insert_keyed(account("ada", 10)).unwrap();
+            assert!(matches!(
+                accounts.insert_keyed(account("ada", 0)),
+                Err(TErrors::DuplicateKey)
+            ));
+            let replaced: Option<Account> = accounts.upsert_keyed(account("ada", 20)).unwrap();
+            assert_eq!(replaced, Some(account("ada", 10)));
+            assert_eq!(accounts.get("ada").unwrap(), Some(account("ada", 20)));
+
+            let tickets: Table<Ticket> = db.table("tickets".to_string());
+            let mut ids: Vec<String> = Vec::new();
+            for title in ["a", "b", "c"] {
+                let ticket: Ticket = Ticket {
+                    title: title.to_string(),
+                    ..Ticket::default()
+                };
+                ids.push(tickets.insert_generated(ticket).unwrap());
+            }
+            assert_eq!(ids, vec!["1", "2", "3"]);
+            assert_eq!(tickets.get("2").unwrap().unwrap().id, "2");
+            // a removed key is never handed out again
+            tickets.remove("3").unwrap();
+            assert_eq!(tickets.insert_generated(Ticket::default()).unwrap(), "4");
 
-            atomic_logger.document()?; 
+            let sessions: Table<Session> = db.table("sessions".to_string());
+            let first: String = sessions.insert_generated(Session::default()).unwrap();
+            let second: String = sessions.insert_generated(Session::default()).unwrap();
+            assert_ne!(first, second);
+            assert_eq!(first.len(), 36);
+            assert_eq!(first.as_bytes()[14], b'4');
+            assert_eq!(sessions.get(&first).unwrap().unwrap().id, first);
+        }
 
-            Ok(self)
+        /// names of the accounts in the order the query returned them
+        fn account_names(fragments: Vec<Fragment<Account>>) -> Vec<String> {
+            fragments.into_iter().map(|f| f.inner.name).collect()
         }
 
-        /// deletes the table in question
-        pub fn delete_table(&self, table_name: String) -> std::io::Result<()> {
-            let path: &String = &format!("./db_files/{}.json", table_name);
+        #[test]
+        fn queries_filter_sort_and_page_the_tables() {
+            let db: Database = Database::in_memory();
+            for (name, balance) in [("ada", 10), ("bob", 5), ("cy", 20), ("dan", 5)] {
+                write(&db, name, account(name, balance));
+            }
+            let run = |query: Query<Account>| account_names(query.run(&db).unwrap());
+            let by_name = || Query::<Account>::new().order_by("name");
 
-            let convert_path: &Path = Path::new(path);
+            assert_eq!(run(by_name().eq("balance", 5)), vec!["bob", "dan"]);
+            assert_eq!(run(by_name().ne("balance", 5)), vec!["ada", "cy"]);
+            assert_eq!(run(by_name().lt("balance", 10)), vec!["bob", "dan"]);
+            assert_eq!(run(by_name().le("balance", 10)), vec!["ada", "bob", "dan"]);
+            assert_eq!(run(by_name().gt("balance", 10)), vec!["cy"]);
+            assert_eq!(run(by_name().ge("balance", 10)), vec!["ada", "cy"]);
+            assert_eq!(run(by_name().contains("name", "a")), vec!["ada", "dan"]);
+            assert_eq!(run(by_name().starts_with("name", "c")), vec!["cy"]);
+            assert_eq!(
+                run(by_name().in_list("name", vec!["cy", "ada", "eve"])),
+                vec!["ada", "cy"]
+            );
 
-            if convert_path.is_file() {
-                std::fs::remove_file(path)?
-            }
+            let low: Query<Account> = Query::new().lt("balance", 10);
+            let rich: Query<Account> = Query::new().gt("balance", 15);
+            assert_eq!(
+                run(low.or(&rich).order_by("name")),
+                vec!["bob", "cy", "dan"]
+            );
+            assert_eq!(run(low.or(&rich).not().order_by("name")), vec!["ada"]);
+            let dan: Query<Account> = Query::new().eq("name", "dan");
+            assert_eq!(run(low.and(&dan)), vec!["dan"]);
 
-            Ok(())
+            // ties on balance are broken by the next order
+            let ordered = Query::<Account>::new()
+                .order_by_desc("balance")
+                .order_by("name");
+            assert_eq!(run(ordered.clone()), vec!["cy", "ada", "bob", "dan"]);
+            assert_eq!(run(ordered.offset(1).limit(2)), vec!["ada", "bob"]);
+            assert!(run(ordered.offset(4)).is_empty());
+
+            let fragment: Fragment<Account> = db.fragment(Account::default());
+            let found: Vec<Fragment<Account>> =
+                fragment.query(&by_name().eq("balance", 5)).unwrap();
+            assert_eq!(account_names(found), vec!["bob", "dan"]);
         }
-        /// deletes table if tables contents match T of Fragment<T>
-        pub fn delete_table_infer(&self) -> Result<(), TErrors>
-        where
-            Fragment<T>: DeserializeOwned,
-        {
-            let Ok(dir) = fs::read_dir("./db_files/") else {
-                return Err(TErrors::DirError);
+
+        #[test]
+        fn queries_match_nulls_and_array_elements() {
+            let db: Database = Database::in_memory();
+            let named: Profile = Profile {
+                name: "bob".to_string(),
+                nickname: Some("b".to_string()),
+                tags: vec!["art".to_string()],
+                ..profile()
             };
-            for entry in dir.into_iter().filter_map(|f| f.ok()) {
-                let mut context = [0; 10];
-                let Ok(mut file) = File::open(entry.path()) else {
-                    return Err(TErrors::FileError);
-                };
-                file.read(&mut context).map_err(|_| {
-                    return TErrors::ReadByteError;
-                })?;
-                let string_context: String = String::from_utf8_lossy(&context).to_string();
-                if let Ok(_) = serde_json::from_str::<Self>(&string_context) {
-                    fs::remove_file(entry.path()).map_err(|_| {
-                        return TErrors::DeleteError;
-                    })?;
-                } else {
-                    continue;
-                }
+            db.fragment(profile())
+                .create_table("ada".to_string())
+                .unwrap();
+            db.fragment(named).create_table("bob".to_string()).unwrap();
+
+            let names = |query: Query<Profile>| -> Vec<String> {
+                let found: Vec<Fragment<Profile>> = query.run(&db).unwrap();
+                found.into_iter().map(|f| f.inner.name).collect()
+            };
+            assert_eq!(names(Query::new().is_null("nickname")), vec!["ada"]);
+            assert_eq!(
+                names(Query::new().is_null("missing").order_by("name")),
+                vec!["ada", "bob"]
+            );
+            assert_eq!(names(Query::new().contains("tags", "art")), vec!["bob"]);
+            assert_eq!(
+                names(Query::new().eq("owner.name", "bob").order_by("name")),
+                vec!["ada", "bob"]
+            );
+        }
+
+        #[test]
+        fn table_queries_return_keys_and_records() {
+            let db: Database = Database::in_memory();
+            let accounts: Table<Account> = db.table("accounts".to_string());
+            for (name, balance) in [("ada", 10), ("bob", 5), ("cy", 20)] {
+                accounts
+                    .insert(name.to_string(), account(name, balance))
+                    .unwrap();
             }
-            Ok(())
+
+            let query: Query<Account> = Query::new().ge("balance", 10).order_by_desc("balance");
+            assert_eq!(
+                accounts.query(&query).unwrap(),
+                vec![
+                    ("cy".to_string(), account("cy", 20)),
+                    ("ada".to_string(), account("ada", 10))
+                ]
+            );
+            assert!(
+                accounts
+                    .query(&Query::new().eq("name", "eve"))
+                    .unwrap()
+                    .is_empty()
+            );
         }
 
-        /// returns all regardless of type of T
-        pub fn get_all(&self) -> Result<Vec<HashMap<String, String>>, TErrors> {
-            let mut temp_vec: Vec<HashMap<String, String>> = Vec::new();
-            for entry in fs::read_dir("./db_files/")
-                .map_err(|_| {
-                    return TErrors::DirError;
-                })?
-                .into_iter()
-                .filter_map(|f| f.ok())
-                .collect::<Vec<DirEntry>>()
-            {
-                let Ok(obj) = self.read_table(entry.path().to_string_lossy().to_string()) else {
-                    return Err(TErrors::ReadByteError);
-                };
+        #[test]
+        fn aggregates_count_sum_and_average_numeric_fields() {
+            let accounts: Collection<Account> = Collection::new(vec![
+                account("ada", 10),
+                account("bob", 5),
+                account("cy", 30),
+            ]);
+            assert_eq!(accounts.count().unwrap(), 3);
+            assert_eq!(accounts.sum("balance").unwrap(), 45.0);
+            assert_eq!(accounts.min("balance").unwrap(), Some(5.0));
+            assert_eq!(accounts.max("balance").unwrap(), Some(30.0));
+            assert_eq!(accounts.avg("balance").unwrap(), Some(15.0));
 
-                let Ok(obj_hash) = obj.to_hash() else {
-                    return Err(TErrors::HashConvert);
-                };
-                temp_vec.push(obj_hash);
+            // names arent numbers, so there is nothing to aggregate
+            assert_eq!(accounts.sum("name").unwrap(), 0.0);
+            assert_eq!(accounts.min("name").unwrap(), None);
+            assert_eq!(accounts.avg("missing").unwrap(), None);
+
+            let empty: Collection<Account> = Collection::new(Vec::new());
+            assert_eq!(empty.count().unwrap(), 0);
+            assert_eq!(empty.max("balance").unwrap(), None);
+        }
+
+        #[test]
+        fn group_by_splits_records_by_value_in_order() {
+            let db: Database = Database::in_memory();
+            let accounts: Table<Account> = db.table("accounts".to_string());
+            for (key, name, balance) in [("1", "ada", 10), ("2", "bob", 5), ("3", "ada", 30)] {
+                accounts
+                    .insert(key.to_string(), account(name, balance))
+                    .unwrap();
             }
-            Ok(temp_vec)
+            assert_eq!(accounts.count().unwrap(), 3);
+            assert_eq!(accounts.sum("balance").unwrap(), 45.0);
+
+            let groups: Vec<Group> = accounts.group_by("name").unwrap();
+            let keys: Vec<&FieldValue> = groups.iter().map(|g| &g.key).collect();
+            assert_eq!(keys, vec![&json!("ada"), &json!("bob")]);
+            assert_eq!(groups[0].count().unwrap(), 2);
+            assert_eq!(groups[0].avg("balance").unwrap(), Some(20.0));
+            assert_eq!(groups[1].sum("balance").unwrap(), 5.0);
+
+            // records without the field share the null group
+            let missing: Vec<Group> = accounts.group_by("missing").unwrap();
+            assert_eq!(missing.len(), 1);
+            assert_eq!(missing[0].key, FieldValue::Null);
+            assert_eq!(missing[0].count().unwrap(), 3);
         }
 
-        /// returns only matching initial type of T
-        pub fn get_all_infer(&self) -> Result<Vec<Fragment<T>>, TErrors> {
-            let mut temp_vec: Vec<Fragment<T>> = Vec::new();
+        #[test]
+        fn tables_named_like_bookkeeping_files_are_refused() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            let before: Vec<String> = db.backend().list().unwrap();
 
-            for entry in fs::read_dir("./db_files/")
-                .map_err(|_| {
-                    return TErrors::DirError;
-                })?
-                .into_iter()
-                .filter_map(|f| f.ok())
-            {
-                if let Ok(obj) = self.read_table(entry.path().to_string_lossy().to_string()) {
-                    temp_vec.push(obj);
-                } else {
-                    continue;
-                }
+            for name in ["wal", "logs", "manifest", "logs@01", "a@b", "a@99"] {
+                let ada: Fragment<Account> = db.fragment(account("ada", 10));
+                assert!(
+                    matches!(
+                        ada.create_table(name.to_string()),
+                        Err(TErrors::ReservedName)
+                    ),
+                    "{name}"
+                );
+                assert!(matches!(
+                    ada.delete_table(name.to_string()),
+                    Err(TErrors::ReservedName)
+                ));
+                assert!(matches!(
+                    db.transaction().stage(name.to_string(), &account("ada", 1)),
+                    Err(TErrors::ReservedName)
+                ));
             }
+            let logs: Table<u64> = db.table(LOG_TABLE.to_string());
+            assert!(matches!(
+                logs.insert("k".to_string(), 1),
+                Err(TErrors::ReservedName)
+            ));
+            assert!(matches!(logs.next_sequence(), Err(TErrors::ReservedName)));
 
-            Ok(temp_vec)
+            assert_eq!(db.backend().list().unwrap(), before);
+            assert_eq!(db.log::<Account>("ada").unwrap().len(), 1);
+            assert_eq!(db.tables().unwrap(), vec!["ada".to_string()]);
         }
 
-        /// combines 2 tables
-        pub fn merge(
-            &self,
-            foreign_table: HashMap<String, String>,
-        ) -> Result<HashMap<String, String>, TErrors> {
-            let Ok(mut hashed_self) = self.to_hash() else {
-                return Err(TErrors::HashConvert);
-            };
-            for (key, value) in foreign_table {
-                hashed_self.insert(key, value);
-            }
+        #[test]
+        fn commit_writes_every_staged_table_and_logs_them() {
+            let db: Database = Database::in_memory();
+            db.fragment(account("ada", 10))
+                .create_table("from".to_string())
+                .unwrap();
 
-            Ok(hashed_self)
+            let mut transfer: Transaction = db.transaction();
+            transfer
+                .stage("from".to_string(), &account("ada", 4))
+                .unwrap()
+                .stage("to".to_string(), &account("bob", 6))
+                .unwrap();
+            // not visible before the commit
+            assert_eq!(read(&db, "from"), Some(account("ada", 10)));
+            assert_eq!(read(&db, "to"), None);
+            transfer.commit().unwrap();
+
+            assert_eq!(read(&db, "from"), Some(account("ada", 4)));
+            assert_eq!(read(&db, "to"), Some(account("bob", 6)));
+            assert_eq!(db.log::<Account>("from").unwrap().len(), 2);
+            assert!(db.log::<Account>("to").unwrap()[0].created);
+            assert!(no_temps(&db));
         }
 
-        /// performs a left join on 2 tables
-        pub fn left_join(
-            &self,
-            foreign_table: HashMap<String, Option<String>>,
-        ) -> Result<HashMap<String, String>, TErrors> {
-            let Ok(mut self_hashed) = self.to_hash() else {
-                return Err(TErrors::HashConvert);
-            };
+        #[test]
+        fn abort_and_drop_leave_the_tables_untouched() {
+            let db: Database = Database::in_memory();
+            db.fragment(account("ada", 10))
+                .create_table("from".to_string())
+                .unwrap();
 
-            let key_vals: Vec<(String, String)> = foreign_table
-                .iter()
-                .filter(|(k, _)| self_hashed.contains_key(*k))
-                .map(|(k2, v2)| {
-                    let v2: &Option<String> = if v2.is_some() {
-                        v2
-                    } else {
-                        &Some("None".to_string())
-                    };
-                    return (
-                        format!(
-                            "{}_{}",
-                            k2.clone(),
-                            count_val(foreign_table.iter().collect(), k2.clone())
-                        ),
-                        v2.clone().unwrap_or("".to_string()),
-                    );
-                })
-                .collect();
+            let mut aborted: Transaction = db.transaction();
+            aborted
+                .stage("from".to_string(), &account("ada", 0))
+                .unwrap();
+            aborted.abort().unwrap();
 
-            key_vals.into_iter().for_each(|(k, v)| {
-                self_hashed.insert(k, v);
+            let mut dropped: Transaction = db.transaction();
+            dropped
+                .stage("from".to_string(), &account("ada", 0))
+                .unwrap();
+            drop(dropped);
+
+            assert_eq!(read(&db, "from"), Some(account("ada", 10)));
+            assert_eq!(db.log::<Account>("from").unwrap().len(), 1);
+            assert!(no_temps(&db));
+        }
+
+        #[test]
+        fn failed_commit_restores_the_tables_already_replaced() {
+            let db: Database = Database::new(FailingRename {
+                inner: MemoryBackend::new(),
+                target: Mutex::new("to.json".to_string()),
             });
+            db.fragment(account("ada", 10))
+                .create_table("from".to_string())
+                .unwrap();
+
+            let mut transfer: Transaction = db.transaction();
+            transfer
+                .stage("from".to_string(), &account("ada", 4))
+                .unwrap()
+                .stage("to".to_string(), &account("bob", 6))
+                .unwrap();
+            assert!(transfer.commit().is_err());
 
-            Ok(self_hashed)
+            assert_eq!(read(&db, "from"), Some(account("ada", 10)));
+            assert_eq!(read(&db, "to"), None);
+            assert_eq!(db.log::<Account>("from").unwrap().len(), 1);
+            assert!(no_temps(&db));
+            assert!(db.verify().unwrap().is_clean());
         }
 
-        /// sorts tables based on key + value
-        pub fn build_where(&self, key: String, value: String) -> Result<Vec<Self>, TErrors> {
-            let mut temp_vec: Vec<Fragment<T>> = Vec::new();
-            for entry in fs::read_dir("./db_files/")
-                .map_err(|_| {
-                    return TErrors::DirError;
-                })?
-                .into_iter()
-                .filter_map(|f| f.ok())
-            {
-                let Ok(contents) = self.read_table(entry.path().to_string_lossy().to_string())
-                else {
-                    return Err(TErrors::ReadByteError);
-                };
-                let Ok(hashed_contents) = contents.to_hash() else {
-                    return Err(TErrors::HashConvert);
-                };
-                if hashed_contents.contains_key(&key)
-                    && hashed_contents
-                        .get(&key)
-                        .unwrap_or(&"".to_string())
-                        .trim()
-                        .to_string()
-                        == value.trim().to_string()
-                {
-                    temp_vec.push(contents)
-                }
-            }
-            Ok(temp_vec)
+        #[test]
+        fn restore_brings_back_the_snapshot_and_drops_later_tables() {
+            let db: Database = Database::in_memory();
+            write(&db, "from", account("ada", 10));
+            let snapshot: Database = Database::in_memory();
+            let manifest: Manifest = db.snapshot(&snapshot).unwrap();
+            assert!(manifest.files.contains_key("from.json"));
+            assert!(!snapshot.backend().exists("wal.json"));
+
+            write(&db, "from", account("ada", 3));
+            write(&db, "to", account("bob", 7));
+            db.restore(&snapshot).unwrap();
+
+            assert_eq!(read(&db, "from"), Some(account("ada", 10)));
+            assert_eq!(read(&db, "to"), None);
+            assert!(no_temps(&db));
+            assert!(db.verify().unwrap().is_clean());
         }
 
-        /// update key in table
-        pub fn update_table(
-            &self,
-            table_name: String,
-            key: String,
-            value: String,
-        ) -> Result<T, TErrors> {
-            let Ok(current_table) = self.read_table(table_name) else {
-                return Err(TErrors::FileError);
-            };
+        #[test]
+        fn snapshot_refuses_a_destination_holding_files() {
+            let db: Database = Database::in_memory();
+            write(&db, "from", account("ada", 10));
 
-            let Ok(mut hashed_table) = current_table.to_hash() else {
-                return Err(TErrors::HashConvert);
-            };
+            assert!(matches!(db.snapshot(&db), Err(TErrors::NotEmpty)));
+            assert_eq!(read(&db, "from"), Some(account("ada", 10)));
+            assert!(db.verify().unwrap().is_clean());
+        }
 
-            hashed_table.insert(key, value);
+        #[test]
+        fn restore_of_a_tampered_snapshot_changes_nothing() {
+            let db: Database = Database::in_memory();
+            write(&db, "from", account("ada", 10));
+            let snapshot: Database = Database::in_memory();
+            db.snapshot(&snapshot).unwrap();
+            snapshot.backend().write("from.json", b"{}").unwrap();
 
-            let Ok(hash_to_string) = &serde_json::to_string(&hashed_table) else {
-                return Err(TErrors::StringConvert);
-            };
+            write(&db, "from", account("ada", 3));
+            assert!(matches!(db.restore(&snapshot), Err(TErrors::CheckError)));
+            assert_eq!(read(&db, "from"), Some(account("ada", 3)));
+        }
+
+        #[test]
+        fn restore_keeps_the_temp_files_of_staged_transactions() {
+            let db: Database = Database::in_memory();
+            write(&db, "from", account("ada", 10));
+            let snapshot: Database = Database::in_memory();
+            db.snapshot(&snapshot).unwrap();
+
+            let mut transfer: Transaction = db.transaction();
+            transfer
+                .stage("to".to_string(), &account("bob", 6))
+                .unwrap();
+            db.restore(&snapshot).unwrap();
+            transfer.commit().unwrap();
+
+            assert_eq!(read(&db, "from"), Some(account("ada", 10)));
+            assert_eq!(read(&db, "to"), Some(account("bob", 6)));
+            assert!(no_temps(&db));
+        }
+
+        #[test]
+        fn recover_finishes_a_restore_cut_short() {
+            let db: Database = Database::new(FailingRename {
+                inner: MemoryBackend::new(),
+                // logs are appended, so only a restore renames onto them
+                target: Mutex::new("logs.jsonl".to_string()),
+            });
+            write(&db, "a", account("ada", 10));
+            write(&db, "b", account("bob", 10));
+            let snapshot: Database = Database::in_memory();
+            db.snapshot(&snapshot).unwrap();
+
+            write(&db, "a", account("ada", 1));
+            write(&db, "b", account("bob", 1));
+            write(&db, "c", account("cy", 1));
+            // fails once every table is already replaced
+            assert!(db.restore(&snapshot).is_err());
+
+            let recovery: Recovery = db.recover().unwrap();
+            assert!(recovery.replayed.contains(&"logs".to_string()));
+            assert_eq!(read(&db, "a"), Some(account("ada", 10)));
+            assert_eq!(read(&db, "b"), Some(account("bob", 10)));
+            assert_eq!(read(&db, "c"), None);
+            assert!(no_temps(&db));
+            assert!(db.verify().unwrap().is_clean());
+        }
+
+        /// writes the temp file of record and logs it to the write-ahead log at state,
+        /// as a write cut short by a crash leaves them
+        fn crash_while_writing(db: &Database, table: &str, record: &Account, state: WalState) {
+            let copy: AtomicCopy = AtomicCopy::new(
+                table.to_string(),
+                "json".to_string(),
+                Format::Json.encode(record).unwrap(),
+            )
+            .construct(db)
+            .unwrap();
+            db.wal_open(state, &[copy], &[]).unwrap();
+        }
 
-            let Ok(output) = serde_json::from_str::<T>(hash_to_string) else {
-                return Err(TErrors::StringConvert);
-            };
+        #[test]
+        fn recover_finishes_prepared_writes() {
+            let db: Database = Database::in_memory();
+            db.fragment(account("ada", 10))
+                .create_table("ada".to_string())
+                .unwrap();
+            crash_while_writing(&db, "ada", &account("ada", 20), WalState::Prepared);
 
-            Ok(output)
+            let recovery: Recovery = db.recover().unwrap();
+            assert_eq!(recovery.replayed, vec!["ada".to_string()]);
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+            assert!(db.wal_entries().is_empty());
+            assert!(no_temps(&db));
         }
 
-        /// updates table's key and value if value is type of Vec<String>
-        pub fn update_table_vec(
-            &self,
-            table_name: String,
-            key: String,
-            value: Vec<String>,
-        ) -> Result<T, TErrors> {
-            let Ok(current_table) = self.read_table(table_name) else {
-                return Err(TErrors::FileError);
-            };
+        #[test]
+        fn recover_discards_writes_begun_but_not_prepared() {
+            let db: Database = Database::in_memory();
+            db.fragment(account("ada", 10))
+                .create_table("ada".to_string())
+                .unwrap();
+            crash_while_writing(&db, "ada", &account("ada", 20), WalState::Begin);
 
-            let Ok(mut hashed_table) = current_table.to_hash_vec() else {
-                return Err(TErrors::HashConvert);
-            };
+            let recovery: Recovery = db.recover().unwrap();
+            assert_eq!(recovery.discarded, vec!["ada".to_string()]);
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
+            assert!(db.wal_entries().is_empty());
+            assert!(no_temps(&db));
+        }
 
-            hashed_table.insert(key, value);
+        #[test]
+        fn recover_removes_orphaned_temps_but_keeps_staged_ones() {
+            let db: Database = Database::in_memory();
+            db.backend().write("ada.1-1.temp", b"{}").unwrap();
+            let mut staged: Transaction = db.transaction();
+            staged.stage("bob".to_string(), &account("bob", 5)).unwrap();
 
-            let Ok(hash_to_string) = &serde_json::to_string(&hashed_table) else {
-                return Err(TErrors::HashConvert);
-            };
+            let recovery: Recovery = db.recover().unwrap();
+            assert_eq!(recovery.removed, vec!["ada.1-1.temp".to_string()]);
+            staged.commit().unwrap();
+            assert_eq!(read(&db, "bob"), Some(account("bob", 5)));
+        }
 
-            let Ok(output) = serde_json::from_str::<T>(hash_to_string) else {
-                return Err(TErrors::StringConvert);
-            };
+        #[test]
+        fn index_range_with_bounds_leaving_no_values_is_empty() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "bob", account("bob", 20));
+            for kind in [IndexKind::Hash, IndexKind::Ordered] {
+                let index: SecondaryIndex = db.create_index("balance".to_string(), kind).unwrap();
+                let (low, high): (FieldValue, FieldValue) = (json!(10), json!(20));
+                let inverted = (Bound::Included(&high), Bound::Included(&low));
+                let open_at_one_value = (Bound::Excluded(&low), Bound::Excluded(&low));
+                let half_open_at_one_value = (Bound::Included(&low), Bound::Excluded(&low));
+                for (lower, upper) in [inverted, open_at_one_value, half_open_at_one_value] {
+                    assert!(index.range(lower, upper).is_empty(), "{lower:?} {upper:?}");
+                }
+                assert_eq!(
+                    index.range(Bound::Included(&low), Bound::Included(&low)),
+                    vec!["ada".to_string()]
+                );
+                assert_eq!(
+                    index.range(Bound::Excluded(&low), Bound::Unbounded),
+                    vec!["bob".to_string()]
+                );
+            }
+        }
 
-            Ok(output)
+        #[test]
+        fn queries_see_writes_through_the_index() {
+            let db: Database = Database::in_memory();
+            db.create_index("balance".to_string(), IndexKind::Ordered)
+                .unwrap();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "bob", account("bob", 20));
+            write(&db, "ada", account("ada", 30));
+            db.fragment(Account::default())
+                .delete_table("bob".to_string())
+                .unwrap();
+
+            let rich: Vec<Fragment<Account>> = Query::new().ge("balance", 15).run(&db).unwrap();
+            let rich: Vec<Account> = rich.into_iter().map(|f| f.inner).collect();
+            assert_eq!(rich, vec![account("ada", 30)]);
+            let index: SecondaryIndex = db.index("balance").unwrap().unwrap();
+            assert!(index.get(&json!(10)).is_empty());
+            assert!(index.get(&json!(20)).is_empty());
         }
-    }
 
-    #[derive(Serialize, Deserialize,Clone, Debug, Hash)]
-    /// Designed to apply actions to temp file before permanent.
-    /// permanent file then only changed on successful execution.
-    pub struct AtomicCopy {
-        pub title: String,
-        pub ext: String,
-        pub data: String,
-    }
+        #[test]
+        fn recover_updates_the_indexes_of_a_write_cut_short() {
+            let db: Database = Database::in_memory();
+            db.create_index("balance".to_string(), IndexKind::Hash)
+                .unwrap();
+            write(&db, "ada", account("ada", 10));
+            crash_while_writing(&db, "ada", &account("ada", 20), WalState::Prepared);
 
-    impl Default for AtomicCopy {
-        fn default() -> Self {
-            Self {
-                title: "example".to_string(),
-                ext: "json".to_string(),
-                data: String::new(),
-            }
+            db.recover().unwrap();
+            let index: SecondaryIndex = db.index("balance").unwrap().unwrap();
+            assert_eq!(index.get(&json!(20)), vec!["ada".to_string()]);
+            assert!(index.get(&json!(10)).is_empty());
+            assert!(db.verify().unwrap().stale_indexes.is_empty());
         }
-    }
 
-    impl AtomicCopy {
-        /// creates new instance of Atomic Copy
-        pub fn new(title: String, ext: String, data: String) -> Self {
-            Self { title, ext, data }
+        #[test]
+        fn repair_rebuilds_stale_indexes_from_the_tables() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            db.create_index("balance".to_string(), IndexKind::Ordered)
+                .unwrap();
+            let old: Vec<u8> = db.backend().read("balance.idx").unwrap();
+            write(&db, "bob", account("bob", 20));
+            // as a crash between the table and index writes would leave it
+            db.backend().write("balance.idx", &old).unwrap();
+
+            let stale: Vec<String> = db.verify().unwrap().stale_indexes;
+            assert_eq!(stale, vec!["balance".to_string()]);
+            let report: IntegrityReport = db.repair().unwrap();
+            assert!(report.repaired.contains(&"balance.idx".to_string()));
+            let index: SecondaryIndex = db.index("balance").unwrap().unwrap();
+            assert_eq!(index.kind, IndexKind::Ordered);
+            assert_eq!(index.get(&json!(20)), vec!["bob".to_string()]);
+            assert!(db.verify().unwrap().is_clean());
         }
-        /// sets file title
-        pub fn set_title(&self, title: String) -> Self {
-            Self {
-                title,
-                ..self.clone()
-            }
+
+        /// db with its clock stopped secs seconds after the epoch
+        fn at(db: &Database, secs: u64) -> Database {
+            db.set_clock(ManualClock::new(UNIX_EPOCH + Duration::from_secs(secs)))
         }
-        /// sets file extension
-        pub fn set_ext(&self, ext: String) -> Self {
-            Self {
-                title: self.title.clone(),
-                ext,
-                data: self.data.clone(),
-            }
+
+        /// writes record to table
+        fn write(db: &Database, table: &str, record: Account) {
+            db.fragment(record).create_table(table.to_string()).unwrap();
         }
-        /// creates file and file contents
-        pub fn construct(&self) -> Result<Self, TErrors> {
-            let Ok(mut file) = File::create(format!("./db_files/{}.{}", self.title, "temp")) else {
-                println!("failed to create file under 'construct'");
-                return Err(TErrors::FileError);
-            };
 
-            file.write_all(self.data.as_bytes()).map_err(|_| {
-                return TErrors::WriteByteError;
-            })?;
+        #[test]
+        fn restore_to_a_time_reverses_every_later_change() {
+            let db: Database = Database::in_memory();
+            write(&at(&db, 100), "ada", account("ada", 10));
+            write(&at(&db, 200), "ada", account("ada", 20));
+            write(&at(&db, 200), "bob", account("bob", 5));
+            at(&db, 300)
+                .fragment(Account::default())
+                .delete_table("ada".to_string())
+                .unwrap();
 
-            file.sync_all().map_err(|_| {
-                return TErrors::WriteByteError;
-            })?;
+            let restored: Vec<String> = at(&db, 400)
+                .restore_to(UNIX_EPOCH + Duration::from_secs(150))
+                .unwrap();
+            assert_eq!(restored, vec!["ada".to_string(), "bob".to_string()]);
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
+            assert_eq!(read(&db, "bob"), None);
 
-            Ok(self.clone())
-        }
-        /// replaces temp with permanent file
-        pub fn replace(&self) -> Result<Self, TErrors> {
-            fs::rename(
-                &format!("./db_files/{}.{}", self.title, "temp"),
-                &format!("./db_files/{}.{}", self.title, self.ext),
-            )
-            .map_err(|_| {
-                println!("Failed to rename file");
-                return TErrors::FileError;
-            })?;
-            Ok(self.clone())
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            assert_eq!(log.last().map(|e| e.op), Some(LogOp::Restore));
+            assert!(db.verify().unwrap().is_clean());
         }
-        /// checks whether content is same between temp and permanent file
-        pub fn check(&self) -> Result<Self, TErrors> {
-            let temp_data =
-                fs::read(&format!("./db_files/{}.{}", self.title, "temp")).map_err(|_| {
-                    return TErrors::ReadByteError;
-                })?;
 
-            let current_data = fs::read(&format!("./db_files/{}.{}", self.title, self.ext))
-                .map_err(|_| {
-                    return TErrors::ReadByteError;
-                })?;
+        #[test]
+        fn restoring_to_a_log_id_rebuilds_one_table_or_every_table() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "bob", account("bob", 5));
+            let point: u64 = db.log::<Account>("bob").unwrap()[0].id;
+            write(&db, "ada", account("ada", 20));
+            write(&db, "bob", account("bob", 6));
 
-            if temp_data != current_data {
-                return Err(TErrors::CheckError);
-            }
+            assert!(db.restore_table_to("bob", point).unwrap());
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+            assert_eq!(read(&db, "bob"), Some(account("bob", 5)));
 
-            Ok(self.clone())
+            assert_eq!(db.restore_to(point).unwrap(), vec!["ada".to_string()]);
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
+            // already there
+            assert!(db.restore_to(point).unwrap().is_empty());
         }
-        /// deletes the temp file
-        pub fn destroy(&self) -> Result<(), TErrors> {
-            let temp_path: String = format!("./db_files/{}.{}", self.title, "temp");
-            let file_path: &Path = Path::new(&temp_path);
-            if !file_path.exists() {
-                return Ok(());
+
+        #[test]
+        fn diff_reverts_to_the_old_version_whatever_the_keys_hold() {
+            let versions: [FieldValue; 6] = [
+                json!({"a.b": 1, "a": {"b": 2}}),
+                json!({"a.b": 2, "a": {"b": 2}}),
+                json!({"a": {"b": 3, "c.d": [1, 2]}, "back\\slash": true}),
+                json!({"a": {"b": 3, "c.d": [1, 2, {"e.": null}]}, "": 0}),
+                json!({"list": [[1], [2, 3]], ".": "dot", "\\.": "both"}),
+                json!(7),
+            ];
+            for old in &versions {
+                for new in &versions {
+                    let diff: Diff = Diff::states(Some(old), Some(new));
+                    assert_eq!(&diff.revert(Some(new.clone())), old, "{old} -> {new}");
+                }
+                let deleted: Diff = Diff::states(Some(old), None);
+                assert_eq!(&deleted.revert(None), old, "{old} deleted");
             }
-            fs::remove_file(&format!("./db_files/{}.{}", self.title, "temp")).map_err(|_| {
-                println!("Failed to delete temp file.");
-                return TErrors::FileError;
-            })
         }
-    }
-
-    #[derive(Default, Clone)]
-    /// creates a collection of type T
-    pub struct Collection<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> {
-        pub inner: Vec<T>,
-    }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default + From<AtomicCopy>> Collect<T>
-        for Collection<T>
-    where 
-        AtomicLogger<T>: Hash + Default + Debug + DeserializeOwned,
-        Collection<AtomicLogger<T>>: Debug + Hash + Serialize + DeserializeOwned + From<Vec<u8>>,
-        Collection<T>: Serialize + DeserializeOwned + Debug + Hash,
-        Fragment<T>: Display + ToString
-    {
-        fn new(inner: Vec<T>) -> Self {
-            Self { inner }
-        }
+        #[test]
+        fn restore_to_brings_back_values_under_dotted_keys() {
+            let db: Database = Database::in_memory();
+            let table = |value: FieldValue| {
+                db.fragment(value)
+                    .create_table("dotted".to_string())
+                    .map(|_| ())
+            };
+            table(json!({"a.b": 1, "a": {"b": 1}})).unwrap();
+            let point: RestorePoint = RestorePoint::LogId(1);
+            table(json!({"a.b": 2, "a": {"b": 1}})).unwrap();
 
-        fn collect(&self, frag: Fragment<T>) -> Result<Self, TErrors>
-        where
-            Self: Sized,
-        {
-            let all_inferred: Vec<T> = frag
-                .get_all_infer()
+            db.restore_to(point).unwrap();
+            let restored: FieldValue = db
+                .fragment(FieldValue::Null)
+                .read_table("dotted".to_string())
                 .unwrap()
-                .into_iter()
-                .map(|f| f.inner)
-                .collect();
-
-            Ok(Self {
-                inner: all_inferred,
-            })
+                .inner;
+            assert_eq!(restored, json!({"a.b": 1, "a": {"b": 1}}));
         }
 
-        fn append(&mut self, obj: T) -> Self {
-            self.inner.push(obj);
-            Self {
-                inner: self.inner.clone(),
+        #[test]
+        fn undo_and_redo_walk_the_history_of_a_table() {
+            let db: Database = Database::in_memory();
+            assert!(!db.undo("ada").unwrap());
+            for balance in [10, 20, 30] {
+                write(&db, "ada", account("ada", balance));
             }
+
+            assert!(db.undo("ada").unwrap());
+            assert!(db.undo("ada").unwrap());
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
+            assert!(db.redo("ada").unwrap());
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+
+            // a new change clears what was left to redo
+            write(&db, "ada", account("ada", 40));
+            assert!(!db.redo("ada").unwrap());
+            assert!(db.undo("ada").unwrap());
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            let ops: Vec<LogOp> = log.iter().map(|e| e.op).collect();
+            assert_eq!(ops.iter().filter(|op| **op == LogOp::Undo).count(), 3);
+            assert_eq!(ops.iter().filter(|op| **op == LogOp::Redo).count(), 1);
         }
 
-        fn remove(&self, obj: T) -> Self
-        where
-            T: PartialEq,
-        {
-            Self {
-                inner: self
-                    .inner
-                    .clone()
-                    .into_iter()
-                    .filter(|f| *f != obj)
-                    .collect::<Vec<T>>(),
-            }
+        #[test]
+        fn undoing_a_creation_deletes_the_table() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+
+            assert!(db.undo("ada").unwrap());
+            assert_eq!(read(&db, "ada"), None);
+            assert!(!db.undo("ada").unwrap());
+            assert!(db.redo("ada").unwrap());
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
         }
 
-        fn update_index(&self, index: usize, new_obj: T) -> Self {
-            Self {
-                inner: self
-                    .inner
-                    .clone()
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, mut f)| {
-                        if i == index {
-                            f = new_obj.clone()
-                        }
-                        f
-                    })
-                    .collect::<Vec<T>>(),
-            }
+        #[test]
+        fn rollback_restores_the_state_before_the_logged_write() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "ada", account("ada", 20));
+
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            let rolled_back: AtomicLogger<Account> = log[1].rollback(&db).unwrap();
+            assert_eq!(rolled_back.op, LogOp::Rollback);
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
+            assert_eq!(db.log::<Account>("ada").unwrap().len(), 3);
+
+            // a rollback is logged, so it can be rolled back in turn
+            rolled_back.rollback(&db).unwrap();
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+            assert!(no_temps(&db));
+            assert!(db.verify().unwrap().is_clean());
         }
 
-        fn write_to_file(&self, title: String) -> Result<(), TErrors>
-        where
-            Self: Serialize + DeserializeOwned + Clone + Debug,
-        {
-            let base_path: String = "./db_files/".to_string(); 
-            let path_string: String = format!("{}{}.json", base_path, title);  
-            let wrapped_string: &Path = Path::new(&path_string); 
+        #[test]
+        fn rolling_back_a_creation_deletes_the_table() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
 
-            let mut file: File = if wrapped_string.exists() {
-                File::open(path_string).map_err(|_|{
-                    return TErrors::FileError; 
-                })?
-            } else {
-                File::create(path_string).map_err(|_|{
-                    return TErrors::FileError; 
-                })?
-            };
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            log[0].rollback(&db).unwrap();
+            assert_eq!(read(&db, "ada"), None);
+            assert!(db.tables().unwrap().is_empty());
+            assert!(db.verify().unwrap().is_clean());
+        }
 
-            file.write_all(format!("{:?}", self.clone()).as_bytes()).unwrap();
+        #[test]
+        fn manual_clocks_move_only_when_told_and_format_as_rfc3339() {
+            let clock: ManualClock = ManualClock::new(UNIX_EPOCH);
+            assert_eq!(rfc3339(clock.now()), "1970-01-01T00:00:00.000000000Z");
 
-            file.sync_all().unwrap(); 
+            clock.set(UNIX_EPOCH + Duration::from_secs(951_782_400));
+            assert_eq!(rfc3339(clock.now()), "2000-02-29T00:00:00.000000000Z");
+            clock.set(UNIX_EPOCH + Duration::new(1_709_251_199, 5));
+            assert_eq!(rfc3339(clock.now()), "2024-02-29T23:59:59.000000005Z");
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(rfc3339(clock.now()), "2024-03-01T00:00:00.000000005Z");
 
-            Ok(())
+            let before: SystemTime = UNIX_EPOCH - Duration::from_secs(1);
+            assert_eq!(rfc3339(before), "1970-01-01T00:00:00.000000000Z");
         }
-    }
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default> ToHash for Collection<T> {
-        fn to_hash(&self) -> Result<HashMap<String, String>, TErrors> {
-            return Err(TErrors::None); 
+        #[test]
+        fn log_entries_are_stamped_by_the_clock_of_the_database() {
+            let db: Database = Database::in_memory();
+            write(&at(&db, 100), "ada", account("ada", 10));
+            write(&at(&db, 86_400), "ada", account("ada", 20));
+            assert_eq!(at(&db, 60).time_stamp(), "1970-01-01T00:01:00.000000000Z");
+
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            let stamps: Vec<&str> = log.iter().map(|e| e.time_stamp.as_str()).collect();
+            assert_eq!(
+                stamps,
+                vec![
+                    "1970-01-01T00:01:40.000000000Z",
+                    "1970-01-02T00:00:00.000000000Z"
+                ]
+            );
         }
 
-        fn to_hash_opt(&self) -> Result<HashMap<String, Option<String>>, TErrors> {
-            serde_json::from_value::<HashMap<String, Option<String>>>(serde_json::to_value(self.clone())?).map_err(|_| {
-                return TErrors::HashConvert; 
-            })
+        #[test]
+        fn the_log_skips_a_torn_last_line_until_recover_cuts_it() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "ada", account("ada", 20));
+            let full: Vec<u8> = db.backend().read("logs.jsonl").unwrap();
+            // half of the last entry again, as a crash mid append leaves it
+            let last: &[u8] = full[..full.len() - 1]
+                .rsplit(|b| *b == b'\n')
+                .next()
+                .unwrap();
+            db.backend()
+                .append("logs.jsonl", &last[..last.len() / 2])
+                .unwrap();
+
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            assert_eq!(log.len(), 2);
+            assert_eq!(log[1].prior, account("ada", 10));
+
+            db.recover().unwrap();
+            assert_eq!(db.backend().read("logs.jsonl").unwrap(), full);
+            write(&db, "ada", account("ada", 30));
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            let ids: Vec<u64> = log.iter().map(|e| e.id).collect();
+            assert_eq!(ids, vec![1, 2, 3]);
+            assert_eq!(log[2].prior, account("ada", 20));
         }
 
-        fn to_hash_vec(&self) -> Result<HashMap<String, Vec<String>>, TErrors> {
-            serde_json::from_value::<HashMap<String, Vec<String>>>(serde_json::to_value(self.clone())?).map_err(|_| {
-                return TErrors::HashConvert; 
-            })
+        #[test]
+        fn verify_reports_each_kind_of_damage() {
+            let db: Database = Database::in_memory();
+            for name in ["edited", "garbled", "stale"] {
+                write(&db, name, account(name, 10));
+            }
+            // hand edited, so the checksum no longer matches
+            let edited: &[u8] = br#"{"name":"edited","balance":99}"#;
+            db.backend().write("edited.json", edited).unwrap();
+            // checksum rewritten along with bytes that cant be decoded
+            db.backend().write("garbled.json", b"{not json").unwrap();
+            db.write_sum("garbled", b"{not json").unwrap();
+            // valid and checksummed, but not what the log last wrote
+            let stale: Vec<u8> = Format::Json.encode(&account("stale", 99)).unwrap();
+            db.backend().write("stale.json", &stale).unwrap();
+            db.write_sum("stale", &stale).unwrap();
+            db.backend().write("stale.1-1.temp", b"{}").unwrap();
+
+            let report: IntegrityReport = db.verify().unwrap();
+            assert_eq!(report.corrupt, vec!["edited".to_string()]);
+            assert_eq!(report.unparseable, vec!["garbled".to_string()]);
+            assert_eq!(report.mismatched, vec!["stale".to_string()]);
+            assert_eq!(report.orphaned, vec!["stale.1-1.temp".to_string()]);
+            let read_edited = db
+                .fragment(Account::default())
+                .read_table("edited".to_string());
+            assert!(matches!(read_edited, Err(TErrors::CheckError)));
         }
 
-        fn zip(&self) -> Result<Vec<(String, String)>, TErrors> {
-            let key = self.to_hash_vec()?.keys().collect()[0]; 
-            self.to_hash_vec()?.values().collect()[0].into_iter().map(|f| {
-                (key, f)
-            }).collect()
+        #[test]
+        fn repair_rewrites_broken_tables_from_the_log() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "ada", account("ada", 20));
+            let before: u64 = db.log::<Account>("ada").unwrap()[0].id;
+            db.backend().write("ada.json", b"{}").unwrap();
+            db.backend().write("ada.1-1.temp", b"{}").unwrap();
+            // unknown to the log, so left alone
+            db.backend().write("ghost.json", b"{not json").unwrap();
+
+            let report: IntegrityReport = db.repair().unwrap();
+            assert_eq!(
+                report.repaired,
+                vec!["ada".to_string(), "ada.1-1.temp".to_string()]
+            );
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+            let after: IntegrityReport = db.verify().unwrap();
+            assert_eq!(after.unparseable, vec!["ghost".to_string()]);
+            assert!(after.corrupt.is_empty() && after.orphaned.is_empty());
+
+            // the repair is logged as a change, not as the table being created
+            let log: Vec<AtomicLogger<Account>> = db.log("ada").unwrap();
+            let repaired: &AtomicLogger<Account> = log.last().unwrap();
+            assert!(repaired.op == LogOp::Repair && !repaired.created);
+            db.restore_table_to("ada", before).unwrap();
+            assert_eq!(read(&db, "ada"), Some(account("ada", 10)));
         }
-    }
 
-    /// A simple logger for actions done
-    #[derive(Serialize, Clone, Debug)]
-    pub struct AtomicLogger<T: Serialize + Sized + Clone + Debug + Hash> {
-        pub prior_id: u64,
-        pub later_id: u64,
-        pub prior: T,
-        pub later: Result<AtomicCopy, TErrors>,
-        pub time_stamp: String,
-    }
+        #[test]
+        fn threads_share_one_handle_and_writes_to_a_table_are_serialized() {
+            fn shared<S: Send + Sync>(_: &S) {}
+            let db: Database = Database::in_memory();
+            shared(&db);
 
-    impl<T: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default + From<AtomicCopy>> ToLogAtomic<T>
-        for AtomicLogger<T>
-    where
-        Fragment<T>: Display + ToString,
-        Collection<T>: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default,
-        AtomicLogger<T>: Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default,
-        Collection<AtomicLogger<T>>:
-            Serialize + DeserializeOwned + Sized + Clone + Debug + Hash + Default + From<Vec<u8>>,
-    {
-        fn new(prior: T, later: AtomicCopy, time_stamp: String) -> Self {
-            Self {
-                prior_id: write_hash(prior.clone()), 
-                later_id: write_hash(later.clone()), 
-                prior,
-                later: Ok(later), 
-                time_stamp
+            let writers: Vec<thread::JoinHandle<()>> = (0..8)
+                .map(|w| {
+                    let table: Table<u64> = db.table("counts".to_string());
+                    thread::spawn(move || {
+                        for i in 0..20 {
+                            table.upsert(format!("{w}-{i}"), i).unwrap();
+                            // every writer bumps the same record too
+                            loop {
+                                let version: u64 = table.version("total").unwrap();
+                                let total: u64 = table.get("total").unwrap().unwrap_or_default();
+                                match table.update_if("total".to_string(), total + 1, version) {
+                                    Ok(_) => break,
+                                    Err(TErrors::Conflict) => continue,
+                                    Err(e) => panic!("{e:?}"),
+                                }
+                            }
+                        }
+                    })
+                })
+                .collect();
+            let reader: Table<u64> = db.table("counts".to_string());
+            while !writers.iter().all(|w| w.is_finished()) {
+                assert!(reader.get("total").is_ok());
             }
+            writers.into_iter().for_each(|w| w.join().unwrap());
+
+            assert_eq!(reader.len().unwrap(), 8 * 20 + 1);
+            assert_eq!(reader.get("total").unwrap(), Some(8 * 20));
+            assert!(db.verify().unwrap().is_clean());
         }
 
-        fn set_hash_ids(&self) -> Result<Self, TErrors> {
-            Ok(Self {
-                prior_id: write_hash(self.prior.clone()),
-                later_id: write_hash(self.later.clone()?),
-                prior: self.prior.clone(),
-                later: self.later.clone(),
-                time_stamp: self.time_stamp.clone(),
-            })
+        /// an empty directory for a test that needs real files
+        fn scratch_dir(name: &str) -> PathBuf {
+            let dir: PathBuf =
+                std::env::temp_dir().join(format!("concept_db-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            dir
         }
 
-        fn compare_ids(&self) -> bool {
-            self.prior_id == self.later_id
+        #[test]
+        fn databases_at_different_roots_keep_their_tables_apart() {
+            let (first_dir, second_dir) = (scratch_dir("root-first"), scratch_dir("root-second"));
+            let nested: PathBuf = second_dir.join("nested").join("db");
+            let first: Database = Database::open(&first_dir).unwrap();
+            let second: Database = Database::open(&nested).unwrap();
+            assert!(nested.is_dir());
+
+            write(&first, "ada", account("ada", 10));
+            write(&second, "ada", account("ada", 20));
+            assert!(first_dir.join("ada.json").is_file());
+            assert_eq!(read(&first, "ada"), Some(account("ada", 10)));
+            assert_eq!(read(&second, "ada"), Some(account("ada", 20)));
+            drop((first, second));
+
+            // tables outlive the handle
+            let reopened: Database = Database::open(&first_dir).unwrap();
+            assert_eq!(read(&reopened, "ada"), Some(account("ada", 10)));
+            drop(reopened);
+            fs::remove_dir_all(&first_dir).unwrap();
+            fs::remove_dir_all(&second_dir).unwrap();
         }
 
-        fn document(&self) -> Result<(), TErrors> {
-            let log_file: String = "./db_files/logs.json".to_string();
-            let wrapped_file: &Path = Path::new(&log_file); 
-            let mut buf: Vec<u8> = Vec::new(); 
-            let mut file: File = if wrapped_file.exists() {
-                File::open(log_file.clone()).map_err(|_|{
-                    return TErrors::FileError; 
-                })?
-            } else {
-                File::create(log_file.clone()).map_err(|_|{
-                    return TErrors::FileError; 
-                })?
-            }; 
-            file.read_to_end(&mut buf).unwrap();             
-            let mut current_logs: Collection<AtomicLogger<T>> = buf.into(); 
-            current_logs.inner.append(&mut vec![self.clone()]);
-            file.write_all(format!("{:?}", current_logs).as_bytes()).unwrap();
-            file.sync_all().unwrap(); 
-            Ok(())
+        #[test]
+        fn open_locks_the_directory_against_other_holders() {
+            let dir: PathBuf = scratch_dir("open-locks");
+            // stands in for another process
+            let other: FileBackend = FileBackend::new(&dir);
+
+            let db: Database = Database::open(&dir).unwrap();
+            assert!(other.lock(LOCK_FILE, true, false).unwrap().is_none());
+            drop(db);
+
+            let held: Option<BlobLock> = other.lock(LOCK_FILE, true, false).unwrap();
+            assert!(held.is_some());
+            assert!(matches!(Database::open(&dir), Err(TErrors::Locked)));
+            assert!(matches!(
+                Database::open_with(&dir, OpenMode::FailFast),
+                Err(TErrors::Locked)
+            ));
+            drop(held);
+            assert!(Database::open(&dir).is_ok());
+            fs::remove_dir_all(&dir).unwrap();
         }
 
-        fn set_prior(&self, prior: T) -> Self {
-            Self {
-                prior_id: self.prior_id.clone(),
-                later_id: self.later_id.clone(),
-                prior: prior.clone(),
-                later: self.later.clone(),
-                time_stamp: self.time_stamp.clone(),
-            }
+        #[test]
+        fn default_handle_fails_operations_instead_of_waiting_for_a_held_lock() {
+            let dir: PathBuf = scratch_dir("default-locked");
+            // stands in for another process
+            let other: FileBackend = FileBackend::new(&dir);
+            other.ensure_root().unwrap();
+            let held: Option<BlobLock> = other.lock(LOCK_FILE, true, false).unwrap();
+
+            let db: Database = Database::open_or_unopened(&dir);
+            assert!(!db.is_read_only());
+            let ada: Fragment<Account> = db.fragment(account("ada", 10));
+            assert!(matches!(
+                ada.create_table("ada".to_string()),
+                Err(TErrors::Locked)
+            ));
+            assert!(matches!(
+                ada.read_table("ada".to_string()),
+                Err(TErrors::Locked)
+            ));
+
+            drop(held);
+            write(&Database::open_or_unopened(&dir), "ada", account("ada", 10));
+            fs::remove_dir_all(&dir).unwrap();
         }
 
-        fn set_later(&self, later: AtomicCopy) -> Self {
-            Self {
-                prior_id: self.prior_id.clone(),
-                later_id: self.later_id.clone(),
-                prior: self.prior.clone(),
-                later: Ok(later.clone()),
-                time_stamp: self.time_stamp.clone(),
-            }
+        #[test]
+        fn opening_a_root_twice_in_one_process_shares_the_handle() {
+            let dir: PathBuf = scratch_dir("open-twice");
+            let first: Database = Database::open(&dir).unwrap();
+            let second: Database = Database::open_with(dir.join("."), OpenMode::Wait).unwrap();
+            write(&first, "ada", account("ada", 10));
+            write(&second, "ada", account("ada", 20));
+            assert_eq!(read(&first, "ada"), Some(account("ada", 20)));
+
+            let other: FileBackend = FileBackend::new(&dir);
+            drop(first);
+            assert!(other.lock(LOCK_FILE, true, false).unwrap().is_none());
+            drop(second);
+            assert!(other.lock(LOCK_FILE, true, false).unwrap().is_some());
+            fs::remove_dir_all(&dir).unwrap();
         }
 
-        fn set_time_stamp(&self, time_stamp: String) -> Self {
-            Self {
-                prior_id: self.prior_id.clone(),
-                later_id: self.later_id.clone(),
-                prior: self.prior.clone(),
-                later: self.later.clone(),
-                time_stamp: time_stamp.clone(),
-            }
+        #[test]
+        fn read_only_handles_read_next_to_a_writer_but_never_write() {
+            let dir: PathBuf = scratch_dir("read-only");
+            let writer: Database = Database::open(&dir).unwrap();
+            write(&writer, "ada", account("ada", 10));
+
+            let reader: Database = Database::open_with(&dir, OpenMode::ReadOnly).unwrap();
+            assert!(reader.is_read_only());
+            assert_eq!(read(&reader, "ada"), Some(account("ada", 10)));
+            let written: Result<(), TErrors> = reader
+                .fragment(account("ada", 20))
+                .create_table("ada".to_string())
+                .map(|_| ());
+            assert!(matches!(written, Err(TErrors::ReadOnly)));
+            assert!(matches!(reader.repair(), Err(TErrors::ReadOnly)));
+            drop(writer);
+            fs::remove_dir_all(&dir).unwrap();
         }
 
-        fn raw_changes(&self) -> Result<(Vec<(String, String)>, Vec<(String, String)>), TErrors> {
-            let prior_frag: Fragment<T> = Fragment::new(self.prior.clone());
-            let later_frag: Fragment<T> = Fragment::new(self.later.clone()?.into());
-            let left_vec: Vec<(String, String)> = prior_frag.zip()?;
-            let right_vec: Vec<(String, String)> = later_frag.zip()?;
-            Ok((left_vec, right_vec))
+        #[test]
+        fn every_staged_write_gets_its_own_temp_file() {
+            let db: Database = Database::in_memory();
+            let copy: AtomicCopy =
+                AtomicCopy::new("ada".to_string(), "json".to_string(), b"{}".to_vec());
+            let first: AtomicCopy = copy.construct(&db).unwrap();
+            let second: AtomicCopy = copy.construct(&db).unwrap();
+            assert_ne!(first.temp_name(), second.temp_name());
+
+            first.destroy(&db).unwrap();
+            assert!(db.backend().exists(&second.temp_name()));
+            second.replace(&db).unwrap();
+            assert!(no_temps(&db));
         }
 
-        fn is_success(&self) -> Result<bool, TErrors> {
-            Ok(self.commit()?.success == true)
+        #[test]
+        fn concurrent_writers_to_one_table_never_share_a_temp_file() {
+            let db: Database = Database::in_memory();
+            let writers: Vec<thread::JoinHandle<()>> = (0..8)
+                .map(|w| {
+                    let db: Database = db.clone();
+                    thread::spawn(move || {
+                        for i in 0..20 {
+                            write(&db, "shared", account("ada", w * 100 + i));
+                        }
+                    })
+                })
+                .collect();
+            writers.into_iter().for_each(|w| w.join().unwrap());
+
+            let log: Vec<AtomicLogger<Account>> = db.log("shared").unwrap();
+            assert_eq!(log.len(), 8 * 20);
+            assert!(log.iter().all(|e| e.outcome == Outcome::Success));
+            assert!(no_temps(&db));
+            assert!(db.verify().unwrap().is_clean());
         }
 
-        fn is_failure(&self) -> Result<bool, TErrors> {
-            Ok(self.commit()?.success == false)
+        #[test]
+        fn updating_a_table_changed_since_it_was_read_conflicts() {
+            let db: Database = Database::in_memory();
+            let frag: Fragment<Account> = db.fragment(account("ada", 10));
+            frag.create_table_if("ada".to_string(), 0).unwrap();
+            assert!(matches!(
+                frag.create_table_if("ada".to_string(), 0),
+                Err(TErrors::Conflict)
+            ));
+
+            let version: u64 = db.version("ada").unwrap();
+            assert_eq!(db.log::<Account>("ada").unwrap()[0].later_id, version);
+            let edit = |balance: i64| {
+                frag.update_table_if(
+                    "ada".to_string(),
+                    "balance".to_string(),
+                    json!(balance),
+                    version,
+                )
+            };
+            edit(20).unwrap();
+            // a second edit made from the same read doesnt overwrite the first
+            assert!(matches!(edit(30), Err(TErrors::Conflict)));
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
         }
 
-        fn commit(&self) -> Result<Commit<AtomicCopy>, TErrors> {
-            self.set_hash_ids()?;
-            Ok(Commit::default().determine(self.later.clone(), Err(TErrors::None)))
+        #[test]
+        fn updating_a_record_changed_since_it_was_read_conflicts() {
+            let accounts: Table<Account> = Database::in_memory().table("accounts".to_string());
+            accounts
+                .update_if("ada".to_string(), account("ada", 10), 0)
+                .unwrap();
+            let version: u64 = accounts.version("ada").unwrap();
+            assert!(matches!(
+                accounts.update_if("ada".to_string(), account("ada", 0), 0),
+                Err(TErrors::Conflict)
+            ));
+
+            accounts
+                .update_if("ada".to_string(), account("ada", 20), version)
+                .unwrap();
+            assert!(matches!(
+                accounts.update_if("ada".to_string(), account("ada", 30), version),
+                Err(TErrors::Conflict)
+            ));
+            assert_eq!(accounts.get("ada").unwrap(), Some(account("ada", 20)));
         }
 
-        fn rollback(&self) -> Result<AtomicLogger<T>, TErrors> {
+        #[test]
+        fn a_view_sees_the_database_as_it_was_when_it_opened() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            write(&db, "bob", account("bob", 5));
+            let accounts: Table<u64> = db.table("balances".to_string());
+            accounts.insert("ada".to_string(), 10).unwrap();
 
-            self.set_hash_ids()?;
+            let view: Database = db.read_view().unwrap();
+            write(&db, "ada", account("ada", 20));
+            write(&db, "cat", account("cat", 1));
+            db.fragment(Account::default())
+                .delete_table("bob".to_string())
+                .unwrap();
+            accounts.upsert("ada".to_string(), 20).unwrap();
+            accounts.insert("bob".to_string(), 5).unwrap();
 
-            Ok(Self {
-                prior_id: self.prior_id.clone(),
-                later_id: self.later_id.clone(),
-                prior: self.prior.clone(),
-                later: self.later.clone(),
-                time_stamp: self.time_stamp.clone(),
-            })
+            assert_eq!(read(&view, "ada"), Some(account("ada", 10)));
+            assert_eq!(read(&view, "bob"), Some(account("bob", 5)));
+            assert_eq!(read(&view, "cat"), None);
+            let balances: Vec<(String, u64)> =
+                view.table("balances".to_string()).iter().unwrap().collect();
+            assert_eq!(balances, vec![("ada".to_string(), 10)]);
+            assert_eq!(read(&db, "ada"), Some(account("ada", 20)));
+        }
+
+        #[test]
+        fn views_are_read_only_and_keep_old_versions_only_while_open() {
+            let db: Database = Database::in_memory();
+            write(&db, "ada", account("ada", 10));
+            let view: Database = db.read_view().unwrap();
+            assert!(view.is_read_only());
+            let written: Result<(), TErrors> = view
+                .fragment(account("ada", 20))
+                .create_table("ada".to_string())
+                .map(|_| ());
+            assert!(matches!(written, Err(TErrors::ReadOnly)));
+
+            drop(view);
+            write(&db, "ada", account("ada", 20));
+            assert!(db.versions.views.lock().unwrap().is_empty());
         }
     }
 }
\ No newline at end of file